@@ -0,0 +1,59 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use onitama_move_gen::{gen::Game, perft::TEST_GAME, zobrist::ZobristKeys};
+
+// my pieces spread over the middle of the board, holding elephant and dragon
+#[allow(clippy::unusual_byte_groupings)]
+const OPEN_GAME: Game = Game {
+    my: 0b00000_01010_10101_00000_00000 | 12 << 25,
+    other: 0b00000_00000_01010_00000_00100 | 2 << 25,
+    cards: (1 << 3 | 1 << 8) | (1 << 6 | 1 << 12) << 16,
+    table: 13,
+};
+
+fn bench_forward(c: &mut Criterion) {
+    let mut group = c.benchmark_group("forward");
+    for (name, game) in [("start", TEST_GAME), ("open", OPEN_GAME)].iter() {
+        group.bench_with_input(BenchmarkId::new("children", name), game, |b, game| {
+            b.iter(|| black_box(game).forward().count())
+        });
+        group.bench_with_input(BenchmarkId::new("parents", name), game, |b, game| {
+            b.iter(|| black_box(game).backward().count())
+        });
+        group.bench_with_input(BenchmarkId::new("moves_vec", name), game, |b, game| {
+            b.iter(|| black_box(game).legal_moves_vec().len())
+        });
+        group.bench_with_input(BenchmarkId::new("moves_small", name), game, |b, game| {
+            b.iter(|| black_box(game).legal_moves_small().len())
+        });
+        group.bench_with_input(BenchmarkId::new("status", name), game, |b, game| {
+            b.iter(|| black_box(game).status())
+        });
+        group.bench_with_input(BenchmarkId::new("win", name), game, |b, game| {
+            b.iter(|| black_box(game).is_win())
+        });
+        group.bench_with_input(BenchmarkId::new("win_and_count", name), game, |b, game| {
+            b.iter(|| {
+                let game = black_box(game);
+                (game.is_win(), game.count_moves())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_hash(c: &mut Criterion) {
+    let keys = ZobristKeys::new(0);
+    let mut group = c.benchmark_group("hash");
+    for (name, game) in [("start", TEST_GAME), ("open", OPEN_GAME)].iter() {
+        group.bench_with_input(BenchmarkId::new("fast", name), game, |b, game| {
+            b.iter(|| black_box(game).fast_hash())
+        });
+        group.bench_with_input(BenchmarkId::new("zobrist", name), game, |b, game| {
+            b.iter(|| black_box(game).zobrist_hash(&keys))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_forward, bench_hash);
+criterion_main!(benches);
@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use onitama_move_gen::perft::perft_test;
+use onitama_move_gen::{
+    perft::{perft_test, TEST_GAME},
+    zobrist::ZobristKeys,
+};
 
 fn bench_perft(c: &mut Criterion) {
     let mut group = c.benchmark_group("perft");
@@ -7,6 +12,10 @@ fn bench_perft(c: &mut Criterion) {
         group.bench_with_input(BenchmarkId::new("perft_depth", i), &i, |b, i| {
             b.iter(|| perft_test(*i))
         });
+        group.bench_with_input(BenchmarkId::new("perft_hashed_depth", i), &i, |b, i| {
+            let keys = ZobristKeys::new(0);
+            b.iter(|| TEST_GAME.perft_hashed(&keys, *i, &mut HashMap::new()))
+        });
     }
     group.finish();
 }
@@ -37,12 +37,24 @@ fn shift(m: u32, pos: usize) -> u32 {
     shifted & MASK[pos % 5]
 }
 
+fn mirror(m: u32) -> u32 {
+    let mut res = 0;
+    for row in 0..5 {
+        let bits = (m >> (row * 5)) & 0b11111;
+        res |= (bits.reverse_bits() >> 27) << (row * 5);
+    }
+    res
+}
+
 fn main() {
     let mut shifted = [[0; 25]; 16];
     let mut shifted_r = [[0; 25]; 16];
     let mut shifted_l = [[0; 25]; 16];
     let mut shifted_u = [[0; 25]; 16];
+    let mut mirrored = [0; 16];
+    let mut rotated = [None; 16];
     let mut names = [""; 16];
+    let mut colors = [0u8; 16];
     for card in 0..16 {
         let m = CARDS[card].1;
         let r = CARDS[card].1.reverse_bits() >> 7;
@@ -54,7 +66,16 @@ fn main() {
             shifted_l[card][pos] = m as u64;
             shifted_u[card][pos] = (m as u64) << 32;
         }
+        mirrored[card] = CARDS
+            .iter()
+            .position(|other| other.1 == mirror(CARDS[card].1))
+            .unwrap() as u32;
+        rotated[card] = CARDS
+            .iter()
+            .position(|other| other.1 == r)
+            .map(|i| i as u32);
         names[card] = CARDS[card].0;
+        colors[card] = CARDS[card].2;
     }
 
     let consts = ConstWriter::for_build("lut").unwrap();
@@ -63,6 +84,9 @@ fn main() {
     consts.add_value("SHIFTED_R", "[[u32; 25]; 16]", shifted_r);
     consts.add_value("SHIFTED_L", "[[u64; 25]; 16]", shifted_l);
     consts.add_value("SHIFTED_U", "[[u64; 25]; 16]", shifted_u);
+    consts.add_value("MIRRORED", "[u32; 16]", mirrored);
+    consts.add_value("ROTATED", "[Option<u32>; 16]", rotated);
     consts.add_value("NAMES", "[&str; 16]", names);
+    consts.add_value("COLORS", "[u8; 16]", colors);
     consts.finish();
 }
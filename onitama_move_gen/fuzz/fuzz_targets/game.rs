@@ -0,0 +1,46 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use onitama_move_gen::gen::Game;
+
+// cargo +nightly fuzz run game
+fuzz_target!(|data: &[u8]| {
+    let game = match Game::from_bytes(data) {
+        Some(game) => game,
+        None => return,
+    };
+    if game.validate().is_err() {
+        return;
+    }
+    assert_eq!(Game::from_bytes(&game.to_bytes()), Some(game));
+
+    let count = game.count_moves();
+    assert_eq!(game.forward().count() as u64, count);
+    let moves = game.legal_moves_small();
+    assert_eq!(moves.len() as u64, count);
+    for (&mv, new_game) in moves.iter().zip(game.forward()) {
+        assert_eq!(game.apply(mv), new_game);
+    }
+
+    // a lost game was reached by taking the king, which `backward` does not undo
+    if game.is_loss() {
+        return;
+    }
+    assert_eq!(
+        game.is_win(),
+        game.forward().any(|new_game| new_game.is_loss())
+    );
+    for new_game in game.forward().filter(|new_game| !new_game.is_loss()) {
+        assert_eq!(new_game.validate(), Ok(()));
+        assert!(new_game.backward().any(|(prev_game, take)| {
+            prev_game == game
+                || Game {
+                    other: prev_game.other | take,
+                    ..prev_game
+                } == game
+        }));
+    }
+    for (prev_game, _) in game.backward() {
+        assert_eq!(prev_game.validate(), Ok(()));
+        assert!(prev_game.forward().any(|new_game| new_game == game));
+    }
+});
@@ -0,0 +1,201 @@
+use crate::{
+    gen::{Game, PIECE_MASK},
+    moves::{Move, MoveEffect},
+    ops::{BitIter, CardIter},
+    SHIFTED, SHIFTED_R,
+};
+
+/// Squares each player can move a piece to with one of their cards,
+/// including squares of their own pieces. Both are in the frame of their owner.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Attacks {
+    pub my: u32,
+    pub other: u32,
+    counts: [AttackCount; 2],
+}
+
+/// For every square the number of pieces and cards of one player that reach it, bit sliced.
+/// With 5 pieces and 2 cards there are at most 10, so 4 bits per square are enough.
+/// Counting lets `Attacks::after` take back the squares of a single piece or card.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+struct AttackCount([u32; 4]);
+
+impl AttackCount {
+    #[inline]
+    fn add(&mut self, squares: u32) {
+        let mut carry = squares;
+        for plane in &mut self.0 {
+            let next = *plane & carry;
+            *plane ^= carry;
+            carry = next;
+        }
+    }
+
+    #[inline]
+    fn sub(&mut self, squares: u32) {
+        let mut borrow = squares;
+        for plane in &mut self.0 {
+            let next = !*plane & borrow;
+            *plane ^= borrow;
+            borrow = next;
+        }
+    }
+
+    #[inline]
+    fn squares(&self) -> u32 {
+        self.0[0] | self.0[1] | self.0[2] | self.0[3]
+    }
+}
+
+#[inline]
+fn side_count(pieces: u32, cards: u32) -> AttackCount {
+    let mut count = AttackCount::default();
+    for card in BitIter(cards & 0xffff) {
+        for pos in BitIter(pieces & PIECE_MASK) {
+            count.add(SHIFTED[card as usize][pos as usize]);
+        }
+    }
+    count
+}
+
+impl Attacks {
+    fn from_counts(my: AttackCount, other: AttackCount) -> Self {
+        Attacks {
+            my: my.squares(),
+            other: other.squares(),
+            counts: [my, other],
+        }
+    }
+
+    /// The attacks after `mv` with `effect` is played in `game`, so from the perspective
+    /// of the other player like `game.apply(mv)`. Only the squares of the moved piece,
+    /// of a taken piece and of the swapped card change, everything else is kept.
+    #[inline]
+    pub fn after(self, game: &Game, mv: Move, effect: MoveEffect) -> Attacks {
+        let [mut my, mut other] = self.counts;
+        let (from, to) = (mv.from as usize, mv.to as usize);
+        let mut my_cards = CardIter::new(game.cards & 0xffff);
+        let kept = my_cards.find(|&card| card != mv.card as u32).unwrap() as usize;
+        let (played, table) = (mv.card as usize, effect.card_swapped.0 as usize);
+
+        my.sub(SHIFTED[kept][from]);
+        my.add(SHIFTED[kept][to]);
+        for pos in BitIter(game.my & PIECE_MASK) {
+            let pos = pos as usize;
+            my.sub(SHIFTED[played][pos]);
+            my.add(SHIFTED[table][if pos == from { to } else { pos }]);
+        }
+
+        if effect.captured.is_some() {
+            for card in BitIter(game.cards.wrapping_shr(16)) {
+                other.sub(SHIFTED[card as usize][24 - to]);
+            }
+        }
+        Attacks::from_counts(other, my)
+    }
+}
+
+/// Pieces of `pieces` that can move to `to` with one of `cards`, all in the same frame.
+#[inline]
+fn side_attackers(pieces: u32, cards: u32, to: usize) -> u32 {
+    if pieces & 1 << to != 0 {
+        return 0;
+    }
+    let mut cards = CardIter::new(cards);
+    let reach = SHIFTED_R[cards.next().unwrap() as usize][to]
+        | SHIFTED_R[cards.next().unwrap() as usize][to];
+    reach & pieces & PIECE_MASK
+}
+
+impl Game {
+    pub fn attacks(&self) -> Attacks {
+        Attacks::from_counts(
+            side_count(self.my, self.cards),
+            side_count(self.other, self.cards.wrapping_shr(16)),
+        )
+    }
+
+    /// My pieces that can move to `to`, the inverse of `destinations_from`.
+    /// Empty if one of my pieces is on `to`.
+    #[inline]
+    pub fn my_attackers_of(&self, to: u8) -> u32 {
+        side_attackers(self.my, self.cards, to as usize)
+    }
+
+    /// Pieces of the other player that can move to `to` on their turn.
+    /// `to` is in my frame and the pieces are in the frame of `self.other`.
+    #[inline]
+    pub fn other_attackers_of(&self, to: u8) -> u32 {
+        side_attackers(self.other, self.cards.wrapping_shr(16), 24 - to as usize)
+    }
+
+    /// Attacks of `self.apply(mv)` given the attacks of `self`, see `Attacks::after`.
+    #[inline]
+    pub fn attacks_after(&self, attacks: Attacks, mv: Move) -> (Game, Attacks) {
+        let (new_game, effect) = self.apply_move_detailed(mv);
+        (new_game, attacks.after(self, mv, effect))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use crate::{
+        gen::PIECE_MASK,
+        ops::BitIter,
+        perft::TEST_GAME,
+        setup::{deal, random_games},
+    };
+
+    #[test]
+    fn test_attacks() {
+        let attacks = TEST_GAME.attacks();
+        // ox and boar from the first row
+        assert_eq!(attacks.my, (1 << 10) - 1);
+        // horse and elephant
+        assert_eq!(attacks.other, (1 << 10) - 1);
+    }
+
+    #[test]
+    fn test_attacks_after() {
+        let rng = Rng::with_seed(6);
+        for _ in 0..100 {
+            let mut game = deal(&rng);
+            let mut attacks = game.attacks();
+            for _ in 0..100 {
+                if game.is_loss() {
+                    break;
+                }
+                let moves = game.legal_moves_small();
+                let mv = moves[rng.usize(0..moves.len())];
+                let (new_game, new_attacks) = game.attacks_after(attacks, mv);
+                assert_eq!(new_game, game.apply(mv));
+                assert_eq!(new_attacks, new_game.attacks());
+                game = new_game;
+                attacks = new_attacks;
+            }
+        }
+    }
+
+    #[test]
+    fn test_attackers_of() {
+        let rng = Rng::with_seed(18);
+        for game in random_games(&rng, 1000) {
+            let flipped = game.null_move();
+            for to in 0..25 {
+                let attackers = BitIter(game.my & PIECE_MASK)
+                    .filter(|&from| game.destinations_from(from as u8) & 1 << to != 0)
+                    .fold(0, |acc, from| acc | 1 << from);
+                assert_eq!(game.my_attackers_of(to), attackers);
+                assert_eq!(
+                    game.other_attackers_of(to),
+                    flipped.my_attackers_of(24 - to)
+                );
+            }
+        }
+        // ox and boar only go straight forward from the first row
+        assert_eq!(TEST_GAME.my_attackers_of(5), 1 << 0);
+        assert_eq!(TEST_GAME.my_attackers_of(1), 0);
+    }
+}
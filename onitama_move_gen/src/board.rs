@@ -0,0 +1,418 @@
+use std::fmt::Display;
+
+use crate::{
+    card::{check_distinct, Card, CardError, Color},
+    gen::{Game, PIECE_MASK},
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BoardError {
+    /// The king square stored above the piece bits is off the board.
+    KingSquare(u32),
+    /// A player does not have exactly one king.
+    KingCount(usize),
+    /// A pawn is off the board.
+    PawnSquare(u32),
+    /// A square is occupied by both players.
+    Overlap(u32),
+    /// A player has no pieces or more than five.
+    PieceCount(usize),
+    /// The player to move already won before the last move of the other player.
+    AlreadyWon,
+    /// The table card is not one of the sixteen cards.
+    Table(u32),
+    Cards(CardError),
+}
+
+impl Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::KingSquare(pos) => write!(f, "king square out of range: {}", pos),
+            BoardError::KingCount(count) => write!(f, "expected 1 king, got {}", count),
+            BoardError::PawnSquare(pos) => write!(f, "pawn square out of range: {}", pos),
+            BoardError::Overlap(pos) => write!(f, "square {} is occupied twice", pos),
+            BoardError::PieceCount(count) => write!(f, "expected 1 to 5 pieces, got {}", count),
+            BoardError::AlreadyWon => f.write_str("the player to move already won"),
+            BoardError::Table(card) => write!(f, "table card out of range: {}", card),
+            BoardError::Cards(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+impl From<CardError> for BoardError {
+    fn from(err: CardError) -> Self {
+        BoardError::Cards(err)
+    }
+}
+
+impl Game {
+    /// Checks the invariants that move generation relies on.
+    /// The player to move may have lost, but the game can not have ended before that.
+    pub fn validate(&self) -> Result<(), BoardError> {
+        let my_king = self.my.wrapping_shr(25);
+        let other_king = self.other.wrapping_shr(25);
+        for &king in &[my_king, other_king] {
+            if king >= 25 {
+                return Err(BoardError::KingSquare(king));
+            }
+        }
+
+        let other = (self.other & PIECE_MASK).reverse_bits() >> 7;
+        let both = self.my & other;
+        if both != 0 {
+            return Err(BoardError::Overlap(both.trailing_zeros()));
+        }
+        for &count in &[self.count_pieces(), self.count_other_pieces()] {
+            if count == 0 || count > 5 {
+                return Err(BoardError::PieceCount(count));
+            }
+        }
+        if my_king == 22 || self.other & 1 << other_king == 0 {
+            return Err(BoardError::AlreadyWon);
+        }
+
+        if self.table >= 16 {
+            return Err(BoardError::Table(self.table));
+        }
+        let hands = [self.cards & 0xffff, self.cards.wrapping_shr(16)];
+        for hand in &hands {
+            if hand.count_ones() != 2 {
+                let count = self.cards.count_ones() as usize + 1;
+                return Err(BoardError::Cards(CardError::Count(count)));
+            }
+        }
+        let mut cards = Vec::with_capacity(5);
+        for hand in &hands {
+            cards.extend((0..16).filter(|i| hand & 1 << i != 0).map(Card));
+        }
+        cards.push(Card(self.table));
+        check_distinct(&cards)?;
+        Ok(())
+    }
+}
+
+/// Version of the layout written by `Game::encode`.
+pub const ENCODING_VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The number of bytes does not match the version.
+    Length(usize),
+    /// A version that this crate does not know, most likely from a newer one.
+    Version(u8),
+    Board(BoardError),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Length(len) => write!(f, "unexpected length: {}", len),
+            DecodeError::Version(version) => write!(f, "unknown encoding version: {}", version),
+            DecodeError::Board(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<BoardError> for DecodeError {
+    fn from(err: BoardError) -> Self {
+        DecodeError::Board(err)
+    }
+}
+
+impl Game {
+    /// The four fields as little endian `u32`s in the order `my`, `other`, `cards`, `table`.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        let fields = [self.my, self.other, self.cards, self.table];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields.iter()) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads the layout of `to_bytes`, `None` if `bytes` is not 16 bytes long.
+    /// The game is not checked, call `validate` before generating moves.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Game> {
+        if bytes.len() != 16 {
+            return None;
+        }
+        let mut fields = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        Some(Game {
+            my: fields.next()?,
+            other: fields.next()?,
+            cards: fields.next()?,
+            table: fields.next()?,
+        })
+    }
+}
+
+impl Game {
+    /// `ENCODING_VERSION` followed by the layout of `to_bytes`, for storing games that have
+    /// to be read by later versions of the crate. A new layout gets a new version,
+    /// and `decode` keeps reading the old ones.
+    pub fn encode(&self) -> [u8; 17] {
+        let mut bytes = [0; 17];
+        bytes[0] = ENCODING_VERSION;
+        bytes[1..].copy_from_slice(&self.to_bytes());
+        bytes
+    }
+
+    /// Reads `encode` and checks the game with `validate`.
+    pub fn decode(bytes: &[u8]) -> Result<Game, DecodeError> {
+        match bytes.split_first() {
+            Some((&ENCODING_VERSION, rest)) => {
+                let game = Game::from_bytes(rest).ok_or(DecodeError::Length(bytes.len()))?;
+                game.validate()?;
+                Ok(game)
+            }
+            Some((&version, _)) => Err(DecodeError::Version(version)),
+            None => Err(DecodeError::Length(0)),
+        }
+    }
+}
+
+impl Game {
+    /// Draws the board with the pieces of `viewer` at the bottom.
+    /// Pawns are `b` and `r`, kings are `B` and `R`.
+    /// `to_move` is the color of the player to move, which the game does not know.
+    pub fn display_for(&self, viewer: Color, to_move: Color) -> String {
+        let (near, far) = if viewer == to_move {
+            (self.my, self.other)
+        } else {
+            (self.other, self.my)
+        };
+        let (near_char, far_char) = match viewer {
+            Color::Blue => ('b', 'r'),
+            Color::Red => ('r', 'b'),
+        };
+        let mut res = String::with_capacity(30);
+        for pos in (0..25).rev() {
+            let c = if near & 1 << pos != 0 {
+                piece_char(near, pos, near_char)
+            } else if far & 1 << 24 >> pos != 0 {
+                piece_char(far, 24 - pos, far_char)
+            } else {
+                '.'
+            };
+            res.push(c);
+            if pos % 5 == 0 && pos != 0 {
+                res.push('\n');
+            }
+        }
+        res
+    }
+}
+
+impl Game {
+    /// The board in the frame of the player to move, `board[row][col]` is square `5 * row + col`.
+    /// My pieces are `x` and those of the other player `o`, kings are upper case.
+    pub fn to_board_array(&self) -> [[char; 5]; 5] {
+        let mut board = [['.'; 5]; 5];
+        for (pos, c) in board.iter_mut().flatten().enumerate() {
+            let pos = pos as u32;
+            if self.my & 1 << pos != 0 {
+                *c = piece_char(self.my, pos, 'x');
+            } else if self.other & 1 << 24 >> pos != 0 {
+                *c = piece_char(self.other, 24 - pos, 'o');
+            }
+        }
+        board
+    }
+
+    /// `to_board_array` as text with row 0 on the first line, followed by the king squares.
+    /// Everything is in the frame of the player to move, while `Debug` gives the square
+    /// of the other king in the frame of the other player.
+    pub fn debug_ascii(&self) -> String {
+        let mut res = String::with_capacity(50);
+        for row in self.to_board_array().iter() {
+            res.extend(row.iter());
+            res.push('\n');
+        }
+        res.push_str(&format!(
+            "x: {}, o: {}",
+            self.my_king(),
+            24 - self.other_king()
+        ));
+        res
+    }
+}
+
+/// `pos` is in the frame of the owner of `pieces`.
+fn piece_char(pieces: u32, pos: u32, c: char) -> char {
+    if pieces.wrapping_shr(25) == pos {
+        c.to_ascii_uppercase()
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(TEST_GAME.validate(), Ok(()));
+
+        let cases = [
+            (
+                Game {
+                    my: 0b11111 | 25 << 25,
+                    ..TEST_GAME
+                },
+                BoardError::KingSquare(25),
+            ),
+            (
+                Game {
+                    my: 0b11111 | 1 << 20 | 2 << 25,
+                    ..TEST_GAME
+                },
+                BoardError::Overlap(20),
+            ),
+            (
+                Game {
+                    my: 0b111111 | 2 << 25,
+                    other: 0b11111 | 2 << 25,
+                    ..TEST_GAME
+                },
+                BoardError::PieceCount(6),
+            ),
+            (
+                Game {
+                    my: 0b1111 | 1 << 22 | 22 << 25,
+                    other: 0b11011,
+                    ..TEST_GAME
+                },
+                BoardError::AlreadyWon,
+            ),
+            (
+                Game {
+                    other: 0b11011 | 2 << 25,
+                    ..TEST_GAME
+                },
+                BoardError::AlreadyWon,
+            ),
+            (
+                Game {
+                    table: 16,
+                    ..TEST_GAME
+                },
+                BoardError::Table(16),
+            ),
+            (
+                Game {
+                    cards: TEST_GAME.cards | 1 << 5,
+                    ..TEST_GAME
+                },
+                BoardError::Cards(CardError::Count(6)),
+            ),
+            (
+                Game {
+                    table: 2,
+                    ..TEST_GAME
+                },
+                BoardError::Cards(CardError::Duplicate(Card(2))),
+            ),
+        ];
+        for (game, err) in cases.iter() {
+            assert_eq!(game.validate().as_ref(), Err(err));
+        }
+
+        // the player to move lost their king
+        let game = Game {
+            my: 0b11011 | 2 << 25,
+            ..TEST_GAME
+        };
+        assert!(game.is_loss());
+        assert_eq!(game.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_debug_ascii() {
+        let start = "xxXxx\n.....\n.....\n.....\nooOoo\nx: 2, o: 22";
+        assert_eq!(TEST_GAME.debug_ascii(), start);
+
+        // my king on 12, the other king on 3 in its own frame and a pawn on 0
+        let game = Game {
+            my: 1 << 7 | 1 << 12 | 12 << 25,
+            other: 1 << 0 | 1 << 3 | 3 << 25,
+            ..TEST_GAME
+        };
+        let board = game.to_board_array();
+        assert_eq!(board[2][2], 'X');
+        assert_eq!(board[1][2], 'x');
+        assert_eq!(board[4][1], 'O');
+        assert_eq!(board[4][4], 'o');
+        let ascii = game.debug_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+        for (row, line) in board.iter().zip(lines.iter()) {
+            assert_eq!(row.iter().collect::<String>(), *line);
+        }
+        assert_eq!(lines[5], "x: 12, o: 21");
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = TEST_GAME.to_bytes();
+        assert_eq!(Game::from_bytes(&bytes), Some(TEST_GAME));
+        assert_eq!(&bytes[12..], &[4, 0, 0, 0]);
+        assert_eq!(Game::from_bytes(&bytes[1..]), None);
+        assert_eq!(Game::from_bytes(&[0; 17]), None);
+    }
+
+    #[test]
+    fn test_encode() {
+        let bytes = TEST_GAME.encode();
+        assert_eq!(bytes[0], ENCODING_VERSION);
+        assert_eq!(&bytes[1..], &TEST_GAME.to_bytes());
+        assert_eq!(Game::decode(&bytes), Ok(TEST_GAME));
+        for new_game in TEST_GAME.forward() {
+            assert_eq!(Game::decode(&new_game.encode()), Ok(new_game));
+        }
+
+        let mut unknown = bytes;
+        unknown[0] = 2;
+        assert_eq!(Game::decode(&unknown), Err(DecodeError::Version(2)));
+        assert_eq!(Game::decode(&[]), Err(DecodeError::Length(0)));
+        assert_eq!(Game::decode(&bytes[..16]), Err(DecodeError::Length(16)));
+        // a table card that does not exist
+        let mut bad = bytes;
+        bad[13] = 16;
+        assert_eq!(
+            Game::decode(&bad),
+            Err(DecodeError::Board(BoardError::Table(16)))
+        );
+    }
+
+    #[test]
+    fn test_display_for() {
+        let start = "rrRrr\n.....\n.....\n.....\nbbBbb";
+        assert_eq!(TEST_GAME.display_for(Color::Blue, Color::Blue), start);
+        let flipped = "bbBbb\n.....\n.....\n.....\nrrRrr";
+        assert_eq!(TEST_GAME.display_for(Color::Red, Color::Blue), flipped);
+        assert_eq!(TEST_GAME.display_for(Color::Red, Color::Red), flipped);
+        assert_eq!(TEST_GAME.display_for(Color::Blue, Color::Red), start);
+
+        // kings in the corners, on 0 for me and on 4 for the other player
+        let game = Game {
+            my: 0b11,
+            other: 1 << 0 | 1 << 4 | 4 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(
+            game.display_for(Color::Blue, Color::Blue),
+            "r...R\n.....\n.....\n.....\n...bB"
+        );
+        assert_eq!(
+            game.display_for(Color::Red, Color::Blue),
+            "Bb...\n.....\n.....\n.....\nR...r"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{attack::Attacks, gen::Game, moves::Move};
+
+/// Results for one position that a front end asks for again and again,
+/// computed on the first query. Asking about another game forgets everything,
+/// so the cache always belongs to the position that was queried last.
+/// Positions are compared exactly and not by hash, so a collision can not give wrong moves.
+#[derive(Clone, Default, Debug)]
+pub struct PositionCache {
+    game: Option<Game>,
+    moves: Option<Vec<Move>>,
+    attacks: Option<Attacks>,
+    eval: Option<i8>,
+}
+
+impl PositionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The position the cached results belong to.
+    pub fn game(&self) -> Option<&Game> {
+        self.game.as_ref()
+    }
+
+    fn select(&mut self, game: &Game) {
+        if self.game != Some(*game) {
+            *self = PositionCache {
+                game: Some(*game),
+                ..Self::default()
+            };
+        }
+    }
+
+    /// `Game::legal_moves_vec`, empty if the game is over.
+    pub fn legal_moves(&mut self, game: &Game) -> &[Move] {
+        self.select(game);
+        self.moves.get_or_insert_with(|| {
+            if game.is_loss() {
+                Vec::new()
+            } else {
+                game.legal_moves_vec()
+            }
+        })
+    }
+
+    pub fn attacks(&mut self, game: &Game) -> Attacks {
+        self.select(game);
+        *self.attacks.get_or_insert_with(|| game.attacks())
+    }
+
+    /// The evaluation of `game`, computed with `eval` the first time like `GameTree::eval_with`.
+    pub fn eval_with(&mut self, game: &Game, eval: impl FnOnce(&Game) -> i8) -> i8 {
+        self.select(game);
+        *self.eval.get_or_insert_with(|| eval(game))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_position_cache() {
+        let mut cache = PositionCache::new();
+        assert_eq!(cache.game(), None);
+        assert_eq!(
+            cache.legal_moves(&TEST_GAME),
+            &TEST_GAME.legal_moves_vec()[..]
+        );
+        assert_eq!(cache.attacks(&TEST_GAME), TEST_GAME.attacks());
+        assert_eq!(cache.game(), Some(&TEST_GAME));
+
+        let mut calls = 0;
+        for _ in 0..3 {
+            let eval = cache.eval_with(&TEST_GAME, |game| {
+                calls += 1;
+                game.count_moves() as i8
+            });
+            assert_eq!(eval, 10);
+        }
+        assert_eq!(calls, 1);
+
+        // another position replaces everything
+        let new_game = TEST_GAME.forward().next().unwrap();
+        assert_eq!(
+            cache.legal_moves(&new_game),
+            &new_game.legal_moves_vec()[..]
+        );
+        assert_eq!(cache.attacks(&new_game), new_game.attacks());
+        assert_eq!(cache.eval_with(&new_game, |_| 3), 3);
+        assert_eq!(cache.eval_with(&TEST_GAME, |_| 5), 5);
+        assert_eq!(cache.game(), Some(&TEST_GAME));
+    }
+}
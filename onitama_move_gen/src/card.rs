@@ -0,0 +1,222 @@
+use std::fmt::{Debug, Display};
+
+use crate::{ops::BitIter, COLORS, NAMES, SHIFTED};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color {
+    Blue,
+    Red,
+}
+
+impl Color {
+    /// `"blue"` or `"red"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Color::Blue => "blue",
+            Color::Red => "red",
+        }
+    }
+
+    #[inline]
+    pub fn opponent(self) -> Color {
+        match self {
+            Color::Blue => Color::Red,
+            Color::Red => Color::Blue,
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Rough archetype of a card, see `Card::category` for how it is decided.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CardCategory {
+    /// Reaches forward more often than backward.
+    Aggressive,
+    /// Reaches backward more often than forward.
+    Defensive,
+    /// Forward and backward alike, with a step to the side.
+    Lateral,
+    /// Jumps two squares in some direction.
+    Leaping,
+    /// Forward and backward alike, only diagonally.
+    Balanced,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Card(pub u32);
+
+impl Card {
+    pub fn from_name(name: &str) -> Result<Self, CardError> {
+        let name = name.trim();
+        NAMES
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(name))
+            .map(|i| Card(i as u32))
+            .ok_or_else(|| CardError::Unknown(name.to_string()))
+    }
+
+    #[inline]
+    pub fn name(self) -> &'static str {
+        NAMES[self.0 as usize]
+    }
+
+    /// Steps of the card as `(rows, columns)` for the player holding it,
+    /// with positive rows towards the other player. Sorted by destination square.
+    pub fn offsets(self) -> Vec<(i32, i32)> {
+        BitIter(SHIFTED[self.0 as usize][12])
+            .map(|pos| (pos as i32 / 5 - 2, pos as i32 % 5 - 2))
+            .collect()
+    }
+
+    /// Leaping cards are those with a step of two squares, like tiger and crab.
+    /// The others are aggressive or defensive if they reach forward more or less often
+    /// than backward, and otherwise lateral if they step sideways or balanced if not.
+    pub fn category(self) -> CardCategory {
+        let offsets = self.offsets();
+        if offsets
+            .iter()
+            .any(|&(rows, cols)| rows.abs() == 2 || cols.abs() == 2)
+        {
+            return CardCategory::Leaping;
+        }
+        let forward = offsets.iter().filter(|&&(rows, _)| rows > 0).count();
+        let backward = offsets.iter().filter(|&&(rows, _)| rows < 0).count();
+        if forward > backward {
+            CardCategory::Aggressive
+        } else if forward < backward {
+            CardCategory::Defensive
+        } else if offsets.iter().any(|&(rows, _)| rows == 0) {
+            CardCategory::Lateral
+        } else {
+            CardCategory::Balanced
+        }
+    }
+
+    #[inline]
+    pub fn color(self) -> Color {
+        if COLORS[self.0 as usize] == 0 {
+            Color::Blue
+        } else {
+            Color::Red
+        }
+    }
+}
+
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Debug for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self, f)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CardError {
+    Unknown(String),
+    Duplicate(Card),
+    Count(usize),
+    /// A card index that is not one of the sixteen cards.
+    Index(u32),
+}
+
+impl Display for CardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardError::Unknown(name) => write!(f, "unknown card: {}", name),
+            CardError::Duplicate(card) => write!(f, "duplicate card: {}", card),
+            CardError::Count(count) => write!(f, "expected 5 cards, got {}", count),
+            CardError::Index(index) => write!(f, "card index out of range: {}", index),
+        }
+    }
+}
+
+impl std::error::Error for CardError {}
+
+/// Checks that the cards are different cards out of the sixteen.
+pub fn check_distinct(cards: &[Card]) -> Result<(), CardError> {
+    for (i, card) in cards.iter().enumerate() {
+        if card.0 >= 16 {
+            return Err(CardError::Index(card.0));
+        }
+        if cards[..i].contains(card) {
+            return Err(CardError::Duplicate(*card));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        for (i, name) in NAMES.iter().enumerate() {
+            assert_eq!(Card::from_name(name), Ok(Card(i as u32)));
+            assert_eq!(Card(i as u32).name(), *name);
+        }
+        assert_eq!(Card::from_name(" Tiger"), Ok(Card(5)));
+        assert_eq!(
+            Card::from_name("lion"),
+            Err(CardError::Unknown("lion".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(Card::from_name("tiger").unwrap().color(), Color::Blue);
+        assert_eq!(Card::from_name("dragon").unwrap().color(), Color::Red);
+        assert_eq!(Color::Blue.opponent(), Color::Red);
+        assert_eq!(Color::Red.opponent(), Color::Blue);
+        assert_eq!(Color::Blue.to_string(), "blue");
+        assert_eq!(Color::Red.name(), "red");
+    }
+
+    #[test]
+    fn test_offsets() {
+        let ox = Card::from_name("ox").unwrap();
+        assert_eq!(ox.offsets(), vec![(-1, 0), (0, -1), (1, 0)]);
+        let tiger = Card::from_name("tiger").unwrap();
+        assert_eq!(tiger.offsets(), vec![(-1, 0), (2, 0)]);
+    }
+
+    #[test]
+    fn test_category() {
+        use CardCategory::*;
+        let categories = [
+            ("ox", Lateral),
+            ("boar", Aggressive),
+            ("horse", Lateral),
+            ("elephant", Aggressive),
+            ("crab", Leaping),
+            ("tiger", Leaping),
+            ("monkey", Balanced),
+            ("crane", Defensive),
+            ("dragon", Leaping),
+            ("mantis", Aggressive),
+            ("frog", Leaping),
+            ("rabbit", Leaping),
+            ("goose", Lateral),
+            ("rooster", Lateral),
+            ("eel", Lateral),
+            ("cobra", Lateral),
+        ];
+        for &(name, category) in &categories {
+            assert_eq!(
+                Card::from_name(name).unwrap().category(),
+                category,
+                "{}",
+                name
+            );
+        }
+    }
+}
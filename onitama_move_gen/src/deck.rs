@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use crate::{card::Card, SHIFTED};
+
+/// A card read from a definition, with its steps as `(rows, columns)` like `Card::offsets`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CardDef {
+    pub name: String,
+    pub offsets: Vec<(i32, i32)>,
+}
+
+impl CardDef {
+    /// Destinations of the card from every square, like a row of `SHIFTED`.
+    pub fn shifted(&self) -> [u32; 25] {
+        let mut shifted = [0; 25];
+        for (pos, destinations) in shifted.iter_mut().enumerate() {
+            let (row, col) = (pos as i32 / 5, pos as i32 % 5);
+            for &(rows, cols) in &self.offsets {
+                let (to_row, to_col) = (row + rows, col + cols);
+                if (0..5).contains(&to_row) && (0..5).contains(&to_col) {
+                    *destinations |= 1 << (to_row * 5 + to_col);
+                }
+            }
+        }
+        shifted
+    }
+
+    /// The built in card with the same steps, which the move generator can play.
+    pub fn card(&self) -> Option<Card> {
+        let shifted = self.shifted();
+        SHIFTED
+            .iter()
+            .position(|table| *table == shifted)
+            .map(|i| Card(i as u32))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CardDefError {
+    /// A line that is not `name: (x, y), ...`, with its number.
+    Syntax(usize, String),
+    /// A step that does not stay on the card grid or does not move, with its line number.
+    Offset(usize, (i32, i32)),
+    /// A card that is defined twice, with the line number of the second definition.
+    Duplicate(usize, String),
+}
+
+impl Display for CardDefError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardDefError::Syntax(number, line) => {
+                write!(f, "line {} is not a card definition: {}", number, line)
+            }
+            CardDefError::Offset(number, (x, y)) => {
+                write!(f, "line {} has a step out of range: ({},{})", number, x, y)
+            }
+            CardDefError::Duplicate(number, name) => {
+                write!(f, "line {} defines {} again", number, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CardDefError {}
+
+/// Custom cards for variants, read from text with one card per line:
+/// ```text
+/// # steps as (columns, rows) from the piece, forward rows are positive
+/// tiger: (0,2),(0,-1)
+/// ```
+/// Steps go at most two squares in each direction, the size of a card.
+/// Empty lines and lines starting with `#` are skipped.
+///
+/// The move tables are generated at build time, so only cards with the steps of
+/// a built in card can be played, see `CardDef::card`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CardSet {
+    pub cards: Vec<CardDef>,
+}
+
+fn parse_offset(step: &str) -> Option<(i32, i32)> {
+    let (x, y) = step
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+impl CardSet {
+    pub fn from_definitions(text: &str) -> Result<CardSet, CardDefError> {
+        let mut set = CardSet::default();
+        for (i, line) in text.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let syntax = || CardDefError::Syntax(number, line.to_string());
+            let (name, steps) = line.split_once(':').ok_or_else(syntax)?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(syntax());
+            }
+            if set.get(name).is_some() {
+                return Err(CardDefError::Duplicate(number, name.to_string()));
+            }
+
+            let mut offsets = Vec::new();
+            // split after each closing parenthesis, the steps contain commas themselves
+            for step in steps.split_inclusive(')') {
+                let step = step.trim().trim_start_matches(',');
+                if step.trim().is_empty() {
+                    continue;
+                }
+                let (x, y) = parse_offset(step).ok_or_else(syntax)?;
+                if x.abs() > 2 || y.abs() > 2 || (x, y) == (0, 0) {
+                    return Err(CardDefError::Offset(number, (x, y)));
+                }
+                if !offsets.contains(&(y, x)) {
+                    offsets.push((y, x));
+                }
+            }
+            if offsets.is_empty() {
+                return Err(syntax());
+            }
+            offsets.sort_unstable();
+            set.cards.push(CardDef {
+                name: name.to_string(),
+                offsets,
+            });
+        }
+        Ok(set)
+    }
+
+    /// The card called `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&CardDef> {
+        self.cards
+            .iter()
+            .find(|def| def.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NAMES;
+
+    #[test]
+    fn test_from_definitions() {
+        let set = CardSet::from_definitions("# comment\n\nTiger: (0,2),(0,-1)\n").unwrap();
+        assert_eq!(set.cards.len(), 1);
+        let tiger = set.get("tiger").unwrap();
+        assert_eq!(tiger.offsets, vec![(-1, 0), (2, 0)]);
+        assert_eq!(tiger.card(), Card::from_name("tiger").ok());
+
+        // a card that is not built in
+        let set = CardSet::from_definitions("lion: (-2,2), (2,2)").unwrap();
+        assert_eq!(set.cards[0].card(), None);
+
+        assert_eq!(
+            CardSet::from_definitions("ox: (1,0)\nox: (0,1)"),
+            Err(CardDefError::Duplicate(2, "ox".to_string()))
+        );
+        assert_eq!(
+            CardSet::from_definitions("\nox: (3,0)"),
+            Err(CardDefError::Offset(2, (3, 0)))
+        );
+        assert_eq!(
+            CardSet::from_definitions("ox: (0,0)"),
+            Err(CardDefError::Offset(1, (0, 0)))
+        );
+        for line in &["ox (1,0)", ": (1,0)", "ox:", "ox: (1,0", "ox: (a,0)"] {
+            assert_eq!(
+                CardSet::from_definitions(line),
+                Err(CardDefError::Syntax(1, line.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_built_in_cards() {
+        // the definitions of all built in cards give the generated tables
+        let mut text = String::new();
+        for (i, name) in NAMES.iter().enumerate() {
+            let steps: Vec<String> = Card(i as u32)
+                .offsets()
+                .iter()
+                .map(|(rows, cols)| format!("({},{})", cols, rows))
+                .collect();
+            text.push_str(&format!("{}: {}\n", name, steps.join(",")));
+        }
+        let set = CardSet::from_definitions(&text).unwrap();
+        assert_eq!(set.cards.len(), 16);
+        for (i, def) in set.cards.iter().enumerate() {
+            assert_eq!(def.shifted(), SHIFTED[i]);
+            assert_eq!(def.card(), Some(Card(i as u32)));
+            assert_eq!(def.offsets, Card(i as u32).offsets());
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::{
     fmt::{Debug, Display},
 };
 
+use crate::gen::{Game, PIECE_MASK};
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Eval(pub i8);
 
@@ -92,11 +94,104 @@ impl Eval {
     // }
 }
 
+/// Temple of the player to move in their own frame, the other player is trying to reach it.
+pub const MY_TEMPLE: u32 = 2;
+/// Temple of the other player in the frame of the player to move.
+pub const OPP_TEMPLE: u32 = 22;
+/// Both temples and the squares next to them, the same in the frame of either player.
+pub const TEMPLE_AREA: u32 = 1 << 1
+    | 1 << 2
+    | 1 << 3
+    | 1 << 6
+    | 1 << 7
+    | 1 << 8
+    | 1 << 16
+    | 1 << 17
+    | 1 << 18
+    | 1 << 21
+    | 1 << 22
+    | 1 << 23;
+
+/// Pieces and attacked squares of the player to move in `TEMPLE_AREA`,
+/// minus those of the other player, each counted in the frame of its owner.
+/// Positive when I contest the other temple and defend my own better than the other player.
+pub fn temple_control(game: &Game) -> i32 {
+    let attacks = game.attacks();
+    let control = |pieces: u32, attacks: u32| {
+        ((pieces & PIECE_MASK & TEMPLE_AREA).count_ones() + (attacks & TEMPLE_AREA).count_ones())
+            as i32
+    };
+    control(game.my, attacks.my) - control(game.other, attacks.other)
+}
+
+/// Empty squares my king can move to that the other player does not attack.
+/// Fewer escape squares means the king is in more danger.
+pub fn king_escape_squares(game: &Game) -> u32 {
+    let attacked = (game.attacks().other | game.other) & PIECE_MASK;
+    let escapes = game.destinations_from(game.my_king()) & !(attacked.reverse_bits() >> 7);
+    escapes.count_ones()
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem::size_of;
 
     use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_temple_area() {
+        assert_ne!(TEMPLE_AREA & 1 << MY_TEMPLE, 0);
+        assert_ne!(TEMPLE_AREA & 1 << OPP_TEMPLE, 0);
+        assert_eq!(TEMPLE_AREA.count_ones(), 12);
+        // the area is the same after turning the board around
+        assert_eq!(TEMPLE_AREA.reverse_bits() >> 7, TEMPLE_AREA);
+    }
+
+    #[test]
+    fn test_temple_control() {
+        // the start is symmetric
+        assert_eq!(temple_control(&TEST_GAME), 0);
+
+        // my king next to the other temple, the other king in the center
+        let game = Game {
+            my: 1 << 0 | 1 << 17 | 17 << 25,
+            other: 1 << 4 | 1 << 12 | 12 << 25,
+            ..TEST_GAME
+        };
+        // my king is in the area and with ox and boar I reach 1, 16, 18 and 22
+        // with horse and elephant the other king reaches 7, 16, 17 and 18
+        // and the other pawn reaches 3 and 8
+        let expected = (1 + 4) - 6;
+        assert_eq!(temple_control(&game), expected);
+    }
+
+    #[test]
+    fn test_king_escape_squares() {
+        // the king can only go forward to 7
+        assert_eq!(TEST_GAME.destinations_from(2), 1 << 7);
+        assert_eq!(king_escape_squares(&TEST_GAME), 1);
+
+        // the king on 12 reaches 7, 11, 13 and 17, the other pawn is on 13
+        // and with horse and elephant the other pieces attack 7 and 17
+        let game = Game {
+            my: 1 << 12 | 12 << 25,
+            other: 1 << 2 | 1 << 11 | 2 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(
+            game.destinations_from(12),
+            1 << 7 | 1 << 11 | 1 << 13 | 1 << 17
+        );
+        assert_eq!(king_escape_squares(&game), 1);
+
+        // a taken king has nowhere to go
+        let game = Game {
+            my: 1 << 0 | 12 << 25,
+            ..game
+        };
+        assert_eq!(king_escape_squares(&game), 0);
+    }
 
     #[test]
     fn test_eval_size() {
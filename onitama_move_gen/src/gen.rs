@@ -8,6 +8,45 @@ use crate::{SHIFTED, SHIFTED_L, SHIFTED_R, SHIFTED_U};
 
 pub const PIECE_MASK: u32 = (1 << 25) - 1;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureKind {
+    Pawn,
+    King,
+}
+
+/// Relative to the player to move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Player {
+    My,
+    Other,
+}
+
+/// Pawns of the player to move and of the other player, the key of a tablebase file.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default)]
+pub struct MaterialSignature(pub u8, pub u8);
+
+impl MaterialSignature {
+    /// `5 * my pawns + other pawns`, below 25.
+    #[inline]
+    pub fn index(self) -> u8 {
+        self.0 * 5 + self.1
+    }
+}
+
+/// Swaps the card `played` in the hand of the player to move for the `table` card.
+/// The hand of the player to move is in the low 16 bits of `cards` like in `Game`.
+/// Undoing a move is the same swap with the two cards exchanged.
+#[inline(always)]
+pub(crate) fn swap_card(cards: u32, played: u32, table: u32) -> u32 {
+    cards ^ 1 << played ^ 1 << table
+}
+
+/// Exchanges the two hands for when the other player is to move, it is its own inverse.
+#[inline(always)]
+pub(crate) fn rotate_hands(cards: u32) -> u32 {
+    cards.wrapping_shl(16) | cards.wrapping_shr(16)
+}
+
 #[derive(Clone, Copy, PartialEq, Hash, Default)]
 pub struct Game {
     pub my: u32,
@@ -16,6 +55,9 @@ pub struct Game {
     pub table: u32,
 }
 
+/// Rows are printed from row 0, the back row of the player to move, with both players
+/// in the frame of the player to move. The other king square in the first line is in
+/// the frame of the other player though, see `debug_ascii` for a view in one frame.
 impl Debug for Game {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!(
@@ -41,6 +83,11 @@ impl Debug for Game {
 }
 
 impl Game {
+    /// Counts both cards at once by packing them into one `u64`.
+    /// The destinations of the first card are in the low 32 bits (`SHIFTED_L`)
+    /// and those of the second card are in the high 32 bits (`SHIFTED_U`).
+    /// Destinations only use the 25 piece bits of each half, so the halves never overlap
+    /// and the king square stored above the piece bits of `my` is masked out by `andn`.
     #[inline(always)]
     pub fn count_moves(&self) -> u64 {
         let mut total = 0;
@@ -54,16 +101,41 @@ impl Game {
                         .get_unchecked(cards.next().unwrap() as usize)
                         .get_unchecked(from as usize)
             };
+            // copy our pieces into both halves to mask out our own pieces for each card
             let my = self.my as u64 | (self.my as u64) << 32;
             total += my.andn(both).popcnt();
         }
         total
     }
 
+    /// Counts the moves that take a piece of the other player, packed like `count_moves`.
     #[inline]
-    pub fn is_win(&self) -> bool {
+    pub fn count_captures(&self) -> u64 {
+        let other = (self.other & PIECE_MASK).reverse_bits() >> 7;
+        let other = other as u64 | (other as u64) << 32;
+        let mut total = 0;
         for from in self.next_my() {
             let both = unsafe {
+                let mut cards = self.next_my_card();
+                SHIFTED_L
+                    .get_unchecked(cards.next().unwrap() as usize)
+                    .get_unchecked(from as usize)
+                    | SHIFTED_U
+                        .get_unchecked(cards.next().unwrap() as usize)
+                        .get_unchecked(from as usize)
+            };
+            total += (both & other).popcnt();
+        }
+        total
+    }
+
+    /// Whether any move takes a piece of the other player.
+    /// The reach of all pieces is combined first so there is only one branch at the end.
+    #[inline]
+    pub fn has_capture(&self) -> bool {
+        let mut reach = 0;
+        for from in self.next_my() {
+            reach |= unsafe {
                 let mut cards = self.next_my_card();
                 SHIFTED
                     .get_unchecked(cards.next().unwrap() as usize)
@@ -72,15 +144,37 @@ impl Game {
                         .get_unchecked(cards.next().unwrap() as usize)
                         .get_unchecked(from as usize)
             };
-            let other_king = 1 << 24 >> self.other.wrapping_shr(25);
-            if both & other_king != 0 {
-                return true;
-            }
-            if from == self.my.wrapping_shr(25) && both & (1 << 22) != 0 {
-                return true;
+        }
+        reach & (self.other & PIECE_MASK).reverse_bits() >> 7 != 0
+    }
+
+    /// The hand always has two cards, so they are read once for all pieces.
+    /// Like `has_capture` the reach of all pieces is combined before checking it,
+    /// which is faster than returning early for the first piece that wins.
+    #[inline]
+    pub fn is_win(&self) -> bool {
+        let mut cards = self.next_my_card();
+        let (first, second) = (cards.next().unwrap(), cards.next().unwrap());
+        let (first, second) = unsafe {
+            (
+                SHIFTED.get_unchecked(first as usize),
+                SHIFTED.get_unchecked(second as usize),
+            )
+        };
+        let my_king = self.my.wrapping_shr(25);
+        let (mut reach, mut king_reach) = (0, 0);
+        for from in self.next_my() {
+            let both =
+                unsafe { first.get_unchecked(from as usize) | second.get_unchecked(from as usize) };
+            reach |= both;
+            // only set while my king is on the board
+            if from == my_king {
+                king_reach = both;
             }
         }
-        false
+        let other_king = 1 << 24 >> self.other.wrapping_shr(25);
+        // my king can not step on the temple if one of my pawns is there
+        reach & other_king != 0 || self.my.andn(king_reach) & (1 << 22) != 0
     }
 
     #[inline]
@@ -88,6 +182,98 @@ impl Game {
         (self.my & PIECE_MASK).popcnt() as usize
     }
 
+    #[inline]
+    pub fn count_other_pieces(&self) -> usize {
+        (self.other & PIECE_MASK).popcnt() as usize
+    }
+
+    /// `to` is in the frame of the player to move.
+    #[inline]
+    pub fn capture_target(&self, to: u8) -> Option<CaptureKind> {
+        let to = to as u32;
+        if self.other & 1 << 24 >> to == 0 {
+            None
+        } else if to + self.other.wrapping_shr(25) == 24 {
+            Some(CaptureKind::King)
+        } else {
+            Some(CaptureKind::Pawn)
+        }
+    }
+
+    /// Square of my king in my frame, it is kept after the king is taken.
+    #[inline]
+    pub fn my_king(&self) -> u8 {
+        self.my.wrapping_shr(25) as u8
+    }
+
+    /// Square of the other king in the frame of the other player,
+    /// the same square in my frame is `24 - other_king`.
+    #[inline]
+    pub fn other_king(&self) -> u8 {
+        self.other.wrapping_shr(25) as u8
+    }
+
+    /// My pieces without the king in my frame.
+    #[inline]
+    pub fn my_pawns(&self) -> u32 {
+        self.my & PIECE_MASK & !(1 << self.my_king())
+    }
+
+    /// The other pieces without the king in the frame of the other player.
+    #[inline]
+    pub fn other_pawns(&self) -> u32 {
+        self.other & PIECE_MASK & !(1 << self.other_king())
+    }
+
+    /// Number of pawns of both players, kings are not counted
+    /// because both are on the board in any game that is not over.
+    #[inline]
+    pub fn material_signature(&self) -> MaterialSignature {
+        MaterialSignature(
+            self.my_pawns().popcnt() as u8,
+            self.other_pawns().popcnt() as u8,
+        )
+    }
+
+    /// The same pieces and player to move, but the players trade their hands.
+    /// For analysis only, like asking how the game would go with the other cards.
+    /// No move leads from `self` to the result, so it is not a position of the same game
+    /// and must not be mixed with `forward`, `backward` or a history of real positions.
+    #[inline]
+    #[must_use]
+    pub fn swap_hands(&self) -> Game {
+        Game {
+            cards: rotate_hands(self.cards),
+            ..*self
+        }
+    }
+
+    /// The position after passing, with the other player to move.
+    /// Passing is not a legal move, this is for asking what the other player could do.
+    #[inline]
+    #[must_use]
+    pub fn null_move(&self) -> Game {
+        Game {
+            my: self.other,
+            other: self.my,
+            cards: rotate_hands(self.cards),
+            table: self.table,
+        }
+    }
+
+    /// Number of king steps to the temple the king is trying to reach,
+    /// which is square 22 in the frame of its owner.
+    #[inline]
+    pub fn king_distance_to_temple(&self, player: Player) -> u8 {
+        let king = match player {
+            Player::My => self.my_king(),
+            Player::Other => self.other_king(),
+        } as i32;
+        let dx = (king % 5 - 2).abs();
+        let dy = (king / 5 - 4).abs();
+        dx.max(dy) as u8
+    }
+
     #[inline]
     pub fn is_loss(&self) -> bool {
         self.other.wrapping_shr(25) == 22 || self.my & 1 << self.my.wrapping_shr(25) == 0
@@ -99,7 +285,7 @@ impl Game {
     }
 
     #[inline]
-    fn next_my(&self) -> BitIter {
+    pub(crate) fn next_my(&self) -> BitIter {
         unsafe { assume(self.my & PIECE_MASK != 0) }
         BitIter(self.my & PIECE_MASK)
     }
@@ -111,17 +297,17 @@ impl Game {
     }
 
     #[inline]
-    fn next_my_card(&self) -> CardIter {
+    pub(crate) fn next_my_card(&self) -> CardIter {
         CardIter::new(self.cards)
     }
 
     #[inline]
-    fn next_other_card(&self) -> CardIter {
+    pub(crate) fn next_other_card(&self) -> CardIter {
         CardIter::new(self.cards.wrapping_shr(16))
     }
 
     #[inline]
-    fn next_to(&self, from: u32, card: u32) -> BitIter {
+    pub(crate) fn next_to(&self, from: u32, card: u32) -> BitIter {
         let &shifted = unsafe {
             SHIFTED
                 .get_unchecked(card as usize)
@@ -131,7 +317,7 @@ impl Game {
     }
 
     #[inline]
-    fn next_from(&self, to: u32, card: u32) -> BitIter {
+    pub(crate) fn next_from(&self, to: u32, card: u32) -> BitIter {
         let &shifted = unsafe {
             SHIFTED_R
                 .get_unchecked(card as usize)
@@ -144,6 +330,63 @@ impl Game {
         BitIter(my_rev.andn(self.other.andn(shifted)))
     }
 
+    #[inline(always)]
+    pub(crate) fn step(&self, from: u32, card: u32, to: u32, my_king: u32) -> Game {
+        let to_other = 1 << 24 >> to;
+        let other = to_other.andn(self.other);
+
+        let cards = rotate_hands(swap_card(self.cards, card, self.table));
+
+        let mut my = self.my ^ (1 << from) ^ (1 << to);
+
+        if from == my_king {
+            my = my & PIECE_MASK | to << 25;
+        };
+
+        Game {
+            other: my,
+            my: other,
+            cards,
+            table: card,
+        }
+    }
+
+    /// The move iterators expect at least one piece, a king on the board,
+    /// two cards in hand and a table card.
+    #[inline]
+    fn can_iterate(&self, pieces: u32, hand: u32) -> bool {
+        pieces & PIECE_MASK != 0
+            && pieces.wrapping_shr(25) < 25
+            && (hand & 0xffff).popcnt() == 2
+            && self.table < 16
+    }
+
+    /// `forward` for games that might be malformed, see `validate` for the full checks.
+    /// Returns `None` instead of panicking when the player to move has no pieces
+    /// or fewer than two cards.
+    #[inline]
+    pub fn try_forward(&self) -> Option<GameIter> {
+        if self.can_iterate(self.my, self.cards) {
+            Some(self.forward())
+        } else {
+            None
+        }
+    }
+
+    /// `backward` for games that might be malformed, like `try_forward`.
+    #[inline]
+    pub fn try_backward(&self) -> Option<GameBackIter> {
+        if self.can_iterate(self.other, self.cards.wrapping_shr(16)) {
+            Some(self.backward())
+        } else {
+            None
+        }
+    }
+
+    /// Expects a valid game, use `try_forward` for games that were not checked.
+    /// Children are ordered by the square of the moved piece, then by the card played,
+    /// then by the destination square, all ascending. This order is stable and
+    /// `legal_moves_small` and the other move lists follow it.
     #[inline]
     pub fn forward(&self) -> GameIter {
         let mut from = self.next_my();
@@ -153,14 +396,32 @@ impl Game {
         let to = self.next_to(from_curr, card_curr);
         GameIter {
             game: self,
+            my_king: self.my.wrapping_shr(25),
             from,
             from_curr,
             card,
             card_curr,
             to,
+            #[cfg(feature = "validate")]
+            yielded: 0,
+            #[cfg(feature = "validate")]
+            valid: !self.is_loss() && self.validate().is_ok(),
         }
     }
 
+    /// Same as `forward`, but stops after the first child where the other player lost.
+    /// The rest of the children do not matter once a win is found, so they are not generated.
+    #[inline]
+    pub fn forward_stop_on_win(&self) -> impl Iterator<Item = Game> + '_ {
+        let mut won = false;
+        self.forward().take_while(move |new_game| {
+            let take = !won;
+            won = new_game.is_loss();
+            take
+        })
+    }
+
+    /// Expects a valid game, use `try_backward` for games that were not checked.
     #[inline]
     pub fn backward(&self) -> GameBackIter {
         let mut to = self.next_other();
@@ -170,6 +431,7 @@ impl Game {
         let from = self.next_from(to_curr, self.table);
         GameBackIter {
             game: self,
+            other_king: self.other.wrapping_shr(25),
             to,
             to_curr,
             card,
@@ -179,13 +441,50 @@ impl Game {
     }
 }
 
+/// Writes `count_moves` of every game into `out`, for a batch of positions in one call.
+/// Panics if `out` does not have the same length as `games`.
+pub fn count_moves_batch(games: &[Game], out: &mut [u64]) {
+    assert_eq!(out.len(), games.len(), "one count for each game");
+    for (count, game) in out.iter_mut().zip(games) {
+        *count = game.count_moves();
+    }
+}
+
 pub struct GameIter<'a> {
     game: &'a Game,
+    my_king: u32,
     from: BitIter,
     from_curr: u32,
     card: CardIter,
     card_curr: u32,
     to: BitIter,
+    #[cfg(feature = "validate")]
+    yielded: u64,
+    /// Children are only checked when the game itself is valid and not finished.
+    #[cfg(feature = "validate")]
+    valid: bool,
+}
+
+#[cfg(feature = "validate")]
+impl GameIter<'_> {
+    fn check_child(&mut self, new_game: &Game) {
+        self.yielded += 1;
+        // a lone king that was taken leaves the player to move without pieces
+        if self.valid && !new_game.is_loss() {
+            if let Err(err) = new_game.validate() {
+                panic!("forward of {:?} gave {:?}: {}", self.game, new_game, err);
+            }
+        }
+    }
+
+    fn check_count(&self) {
+        assert_eq!(
+            self.yielded,
+            self.game.count_moves(),
+            "forward and count_moves disagree on {:?}",
+            self.game
+        );
+    }
 }
 
 impl Iterator for GameIter<'_> {
@@ -197,7 +496,14 @@ impl Iterator for GameIter<'_> {
         while to_new.is_none() {
             let mut card_new = self.card.next();
             if card_new.is_none() {
-                self.from_curr = self.from.next()?;
+                self.from_curr = match self.from.next() {
+                    Some(from) => from,
+                    None => {
+                        #[cfg(feature = "validate")]
+                        self.check_count();
+                        return None;
+                    }
+                };
                 self.card = self.game.next_my_card();
                 card_new = self.card.next();
             }
@@ -207,26 +513,11 @@ impl Iterator for GameIter<'_> {
         }
         let to_curr = to_new.unwrap();
 
-        let my_king = self.game.my.wrapping_shr(25);
-
-        let to_other = 1 << 24 >> to_curr;
-        let other = to_other.andn(self.game.other);
-
-        let my_cards = self.game.cards ^ 1 << self.card_curr ^ 1 << self.game.table;
-        let cards = my_cards.wrapping_shl(16) | my_cards.wrapping_shr(16);
-
-        let mut my = self.game.my ^ (1 << self.from_curr) ^ (1 << to_curr);
-
-        if self.from_curr == my_king {
-            my = my & PIECE_MASK | to_curr << 25;
-        };
-
-        let new_game = Game {
-            other: my,
-            my: other,
-            cards,
-            table: self.card_curr,
-        };
+        let new_game = self
+            .game
+            .step(self.from_curr, self.card_curr, to_curr, self.my_king);
+        #[cfg(feature = "validate")]
+        self.check_child(&new_game);
         Some(new_game)
     }
 }
@@ -239,6 +530,7 @@ impl ExactSizeIterator for GameIter<'_> {
 
 pub struct GameBackIter<'a> {
     game: &'a Game,
+    other_king: u32,
     to: BitIter,
     to_curr: u32,
     card: CardIter,
@@ -265,13 +557,14 @@ impl Iterator for GameBackIter<'_> {
         }
         let from_curr = from_new.unwrap();
 
-        let other_king = self.game.other.wrapping_shr(25);
-
-        let cards = self.game.cards.wrapping_shl(16) | self.game.cards.wrapping_shr(16);
-        let cards = cards ^ 1 << self.card_curr ^ 1 << self.game.table;
+        let cards = swap_card(
+            rotate_hands(self.game.cards),
+            self.game.table,
+            self.card_curr,
+        );
         let mut other = self.game.other ^ (1 << self.to_curr) ^ (1 << from_curr);
 
-        if self.to_curr == other_king {
+        if self.to_curr == self.other_king {
             other = other & PIECE_MASK | from_curr << 25;
         };
 
@@ -284,3 +577,473 @@ impl Iterator for GameBackIter<'_> {
         Some((prev_game, (1 << 24) >> self.to_curr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+    use crate::{card::Card, perft::TEST_GAME, setup::random_games, tablebase::TableBase};
+
+    #[test]
+    fn test_count_pieces() {
+        assert_eq!(TEST_GAME.count_pieces(), 5);
+        assert_eq!(TEST_GAME.count_other_pieces(), 5);
+
+        let game = Game {
+            my: 0b101 | 2 << 25,
+            other: 0b11100 | 3 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(game.count_pieces(), 2);
+        assert_eq!(game.count_other_pieces(), 3);
+        // the king square is stored above the piece bits
+        let game = Game {
+            other: 1 << 24 | 24 << 25,
+            ..game
+        };
+        assert_eq!(game.count_other_pieces(), 1);
+    }
+
+    #[test]
+    fn test_capture_target() {
+        assert_eq!(TEST_GAME.capture_target(12), None);
+        assert_eq!(TEST_GAME.capture_target(22), Some(CaptureKind::King));
+        assert_eq!(TEST_GAME.capture_target(20), Some(CaptureKind::Pawn));
+        assert_eq!(TEST_GAME.capture_target(2), None);
+
+        let no_king = Game {
+            other: TEST_GAME.other ^ 1 << 2,
+            ..TEST_GAME
+        };
+        assert_eq!(no_king.capture_target(22), None);
+        assert_eq!(no_king.capture_target(24), Some(CaptureKind::Pawn));
+    }
+
+    #[test]
+    fn test_king_and_pawns() {
+        assert_eq!(TEST_GAME.my_king(), 2);
+        assert_eq!(TEST_GAME.other_king(), 2);
+        assert_eq!(TEST_GAME.my_pawns(), 0b11011);
+        assert_eq!(TEST_GAME.other_pawns(), 0b11011);
+
+        let game = Game {
+            my: 1 << 3 | 1 << 12 | 12 << 25,
+            other: 1 << 0 | 1 << 6 | 1 << 20 | 6 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(game.my_king(), 12);
+        assert_eq!(game.other_king(), 6);
+        assert_eq!(game.my_pawns(), 1 << 3);
+        assert_eq!(game.other_pawns(), 1 << 0 | 1 << 20);
+        // the other king is on 18 in my frame
+        assert_eq!(game.capture_target(18), Some(CaptureKind::King));
+        assert_eq!(game.capture_target(24), Some(CaptureKind::Pawn));
+
+        // a taken king keeps its square but is not a pawn
+        let game = Game {
+            my: 1 << 3 | 12 << 25,
+            ..game
+        };
+        assert_eq!(game.my_king(), 12);
+        assert_eq!(game.my_pawns(), 1 << 3);
+        assert!(game.is_loss());
+    }
+
+    #[test]
+    fn test_material_signature() {
+        assert_eq!(TEST_GAME.material_signature(), MaterialSignature(4, 4));
+        assert_eq!(TEST_GAME.material_signature().index(), 24);
+
+        let game = Game {
+            my: 1 << 3 | 1 << 12 | 12 << 25,
+            other: 1 << 0 | 1 << 6 | 1 << 20 | 6 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(game.material_signature(), MaterialSignature(1, 2));
+        assert_eq!(game.material_signature().index(), 7);
+        let game = Game {
+            my: 1 << 12 | 12 << 25,
+            ..game
+        };
+        assert_eq!(game.material_signature(), MaterialSignature(0, 2));
+
+        let rng = Rng::with_seed(4);
+        for game in random_games(&rng, 1000) {
+            let MaterialSignature(my, other) = game.material_signature();
+            assert_eq!(my as usize, game.count_pieces() - 1);
+            assert_eq!(other as usize, game.count_other_pieces() - 1);
+            // the tablebase covers the configurations with at most one pawn each
+            assert_eq!(my <= 1 && other <= 1, TableBase::covers(&game));
+        }
+    }
+
+    #[test]
+    fn test_king_distance_to_temple() {
+        assert_eq!(TEST_GAME.king_distance_to_temple(Player::My), 4);
+        assert_eq!(TEST_GAME.king_distance_to_temple(Player::Other), 4);
+
+        for &(king, distance) in &[(22, 0), (21, 1), (17, 1), (12, 2), (0, 4), (4, 4), (24, 2)] {
+            let game = Game {
+                my: 1 << king | king << 25,
+                other: 1 << king | king << 25,
+                ..TEST_GAME
+            };
+            assert_eq!(game.king_distance_to_temple(Player::My), distance);
+            assert_eq!(game.king_distance_to_temple(Player::Other), distance);
+        }
+
+        // the other king on square 2 in my frame is on its own temple
+        let game = Game {
+            my: 1 << 2 | 2 << 25,
+            other: 1 << 22 | 22 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(game.king_distance_to_temple(Player::My), 4);
+        assert_eq!(game.king_distance_to_temple(Player::Other), 0);
+    }
+
+    #[test]
+    fn test_count_moves_packing() {
+        for card in 0..16 {
+            for pos in 0..25 {
+                assert_eq!(SHIFTED[card][pos] & !PIECE_MASK, 0);
+                assert_eq!(SHIFTED_L[card][pos], SHIFTED[card][pos] as u64);
+                assert_eq!(SHIFTED_U[card][pos], (SHIFTED[card][pos] as u64) << 32);
+            }
+        }
+
+        let rng = Rng::with_seed(0);
+        for game in random_games(&rng, 1000) {
+            let mut total = 0;
+            for from in game.next_my() {
+                for card in game.next_my_card() {
+                    let shifted = SHIFTED[card as usize][from as usize];
+                    total += (game.my & PIECE_MASK).andn(shifted).popcnt() as u64;
+                }
+            }
+            assert_eq!(game.count_moves(), total);
+        }
+    }
+
+    #[test]
+    fn test_count_moves_single_piece() {
+        // the destinations of each card clipped at the borders, from its steps
+        let mut clipped = [[0u32; 25]; 16];
+        for (card, destinations) in clipped.iter_mut().enumerate() {
+            for (pos, destination) in destinations.iter_mut().enumerate() {
+                let (row, col) = (pos as i32 / 5, pos as i32 % 5);
+                for (rows, cols) in Card(card as u32).offsets() {
+                    let (to_row, to_col) = (row + rows, col + cols);
+                    if (0..5).contains(&to_row) && (0..5).contains(&to_col) {
+                        *destination |= 1 << (to_row * 5 + to_col);
+                    }
+                }
+                assert_eq!(SHIFTED[card][pos], *destination);
+            }
+        }
+
+        // only my king on the board with every pair of cards, the other king never blocks
+        for first in 0..16 {
+            for second in (0..16).filter(|&second| second != first) {
+                for from in 0..25 {
+                    let other_king = 24 - (from + 12) % 25;
+                    let game = Game {
+                        my: 1 << from | from << 25,
+                        other: 1 << other_king | other_king << 25,
+                        cards: 1 << first | 1 << second,
+                        table: (0..16)
+                            .find(|&table| table != first && table != second)
+                            .unwrap(),
+                    };
+                    let expected = clipped[first as usize][from as usize].count_ones()
+                        + clipped[second as usize][from as usize].count_ones();
+                    assert_eq!(game.count_moves(), expected as u64);
+                    assert_eq!(game.forward().count() as u64, game.count_moves());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_moves_batch() {
+        let rng = Rng::with_seed(14);
+        let games = random_games(&rng, 100);
+        let mut out = vec![0; games.len()];
+        count_moves_batch(&games, &mut out);
+        for (&count, game) in out.iter().zip(&games) {
+            assert_eq!(count, game.count_moves());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "one count for each game")]
+    fn test_count_moves_batch_length() {
+        count_moves_batch(&[TEST_GAME], &mut [0; 2]);
+    }
+
+    #[test]
+    fn test_backward_forward() {
+        let rng = Rng::with_seed(1);
+        for game in random_games(&rng, 1000) {
+            assert_eq!(game.validate(), Ok(()));
+            for (prev_game, take) in game.backward() {
+                assert_eq!(prev_game.validate(), Ok(()));
+                assert!(prev_game.forward().any(|new_game| new_game == game));
+                // the move could also have been a capture if there is room for another piece
+                if prev_game.count_other_pieces() < 5 {
+                    let prev_game = Game {
+                        other: prev_game.other | take,
+                        ..prev_game
+                    };
+                    assert_eq!(prev_game.validate(), Ok(()));
+                    assert!(prev_game.forward().any(|new_game| new_game == game));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_has_capture() {
+        let rng = Rng::with_seed(2);
+        let mut captures = 0;
+        for game in random_games(&rng, 1000) {
+            let count = game
+                .legal_moves_small()
+                .iter()
+                .filter(|mv| game.capture_target(mv.to).is_some())
+                .count();
+            assert_eq!(game.count_captures(), count as u64);
+            assert_eq!(game.has_capture(), game.count_captures() > 0);
+            captures += game.has_capture() as usize;
+        }
+        // both cases are covered
+        assert!(captures > 0 && captures < 1000);
+    }
+
+    #[test]
+    fn test_try_forward() {
+        let rng = Rng::with_seed(3);
+        for game in random_games(&rng, 1000) {
+            assert!(game.try_forward().unwrap().eq(game.forward()));
+            assert_eq!(
+                game.try_backward().unwrap().count(),
+                game.backward().count()
+            );
+        }
+
+        let malformed = [
+            // no pieces
+            Game { my: 0, ..TEST_GAME },
+            // one card in hand
+            Game {
+                cards: TEST_GAME.cards & !1,
+                ..TEST_GAME
+            },
+            // no cards at all
+            Game {
+                cards: 0,
+                ..TEST_GAME
+            },
+            Game {
+                table: 16,
+                ..TEST_GAME
+            },
+            Game {
+                my: TEST_GAME.my & PIECE_MASK | 25 << 25,
+                ..TEST_GAME
+            },
+        ];
+        for game in &malformed {
+            assert!(game.try_forward().is_none());
+        }
+        let game = Game {
+            other: 0,
+            ..TEST_GAME
+        };
+        assert!(game.try_backward().is_none());
+        assert!(game.try_forward().is_some());
+    }
+
+    #[test]
+    fn test_forward_stop_on_win() {
+        let rng = Rng::with_seed(5);
+        let (mut stopped, mut all) = (0, 0);
+        for game in random_games(&rng, 1000) {
+            let children: Vec<Game> = game.forward_stop_on_win().collect();
+            let first_win = game.forward().position(|new_game| new_game.is_loss());
+            match first_win {
+                Some(i) => {
+                    assert_eq!(children.len(), i + 1);
+                    assert!(children[i].is_loss());
+                    assert!(game.is_win());
+                }
+                None => assert_eq!(children.len() as u64, game.count_moves()),
+            }
+            assert!(game.forward().zip(children.iter()).all(|(a, b)| a == *b));
+            if game.is_win() {
+                stopped += children.len() as u64;
+                all += game.count_moves();
+            }
+        }
+        // positions with a win only generate part of their children
+        assert!(stopped < all);
+    }
+
+    #[test]
+    fn test_forward_checked() {
+        // with the `validate` feature `forward` also checks itself while iterating
+        let rng = Rng::with_seed(15);
+        for game in random_games(&rng, 20000) {
+            let mut count = 0;
+            for new_game in game.forward() {
+                count += 1;
+                assert!(new_game.is_loss() || new_game.validate().is_ok());
+            }
+            assert_eq!(count, game.count_moves());
+        }
+    }
+
+    /// `is_win` as it was before the cards were read once, with a card iterator per piece.
+    fn is_win_per_piece(game: &Game) -> bool {
+        for from in game.next_my() {
+            let mut cards = game.next_my_card();
+            let both = SHIFTED[cards.next().unwrap() as usize][from as usize]
+                | SHIFTED[cards.next().unwrap() as usize][from as usize];
+            let other_king = 1 << 24 >> game.other.wrapping_shr(25);
+            if both & other_king != 0 {
+                return true;
+            }
+            if from == game.my.wrapping_shr(25) && game.my.andn(both) & (1 << 22) != 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_is_win_single_piece() {
+        let mut wins = 0;
+        for first in 0..16 {
+            for second in (0..16).filter(|&second| second != first) {
+                for king in 0..25 {
+                    // the other king on every other square, in its own frame
+                    for other_king in (0..25).filter(|&other_king| other_king + king != 24) {
+                        let game = Game {
+                            my: 1 << king | king << 25,
+                            other: 1 << other_king | other_king << 25,
+                            cards: 1 << first | 1 << second,
+                            table: 0,
+                        };
+                        assert_eq!(game.is_win(), is_win_per_piece(&game));
+                        wins += game.is_win() as usize;
+                    }
+                }
+            }
+        }
+        assert!(wins > 0);
+    }
+
+    #[test]
+    fn test_is_win_fuzz() {
+        let rng = Rng::with_seed(16);
+        for game in random_games(&rng, 2000) {
+            assert_eq!(game.is_win(), is_win_per_piece(&game));
+            for new_game in game.forward() {
+                assert_eq!(new_game.is_win(), is_win_per_piece(&new_game));
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_win_temple_occupied() {
+        // ox would bring my king from 23 to the temple, but my pawn is there
+        let game = Game {
+            my: 1 << 22 | 1 << 23 | 23 << 25,
+            other: 1 << 21 | 21 << 25,
+            cards: 0b11 | 0b1100 << 16,
+            table: 4,
+        };
+        assert_eq!(game.validate(), Ok(()));
+        assert!(!game.is_win());
+        assert!(!game.forward().any(|new_game| new_game.is_loss()));
+    }
+
+    #[test]
+    fn test_swap_hands() {
+        let swapped = TEST_GAME.swap_hands();
+        assert_eq!(swapped.cards, 0b1100 | 0b11 << 16);
+        assert_eq!(
+            (swapped.my, swapped.other, swapped.table),
+            (TEST_GAME.my, TEST_GAME.other, 4)
+        );
+
+        let rng = Rng::with_seed(12);
+        for game in random_games(&rng, 1000) {
+            let swapped = game.swap_hands();
+            assert_eq!(swapped.swap_hands(), game);
+            assert_eq!(swapped.validate(), Ok(()));
+            // my pieces move with the cards of the other player
+            let mut total = 0;
+            for from in BitIter(game.my & PIECE_MASK) {
+                for card in BitIter(game.cards >> 16) {
+                    let shifted = SHIFTED[card as usize][from as usize];
+                    total += game.my.andn(shifted).popcnt() as u64;
+                }
+            }
+            assert_eq!(swapped.count_moves(), total);
+        }
+    }
+
+    #[test]
+    fn test_card_rotation() {
+        let mut arrangements = 0;
+        for my1 in 0..16 {
+            for my2 in my1 + 1..16 {
+                for other1 in 0..16 {
+                    for other2 in other1 + 1..16 {
+                        for table in 0..16 {
+                            let used: u32 =
+                                1 << my1 | 1 << my2 | 1 << other1 | 1 << other2 | 1 << table;
+                            if used.count_ones() != 5 {
+                                continue;
+                            }
+                            arrangements += 1;
+                            let cards = 1 << my1 | 1 << my2 | (1 << other1 | 1 << other2) << 16;
+                            assert_eq!(rotate_hands(rotate_hands(cards)), cards);
+                            for &(played, kept) in &[(my1, my2), (my2, my1)] {
+                                let new_cards = rotate_hands(swap_card(cards, played, table));
+                                assert_eq!(new_cards & 0xffff, 1 << other1 | 1 << other2);
+                                assert_eq!(new_cards >> 16, 1 << kept | 1 << table);
+                                assert_eq!(
+                                    swap_card(rotate_hands(new_cards), table, played),
+                                    cards
+                                );
+                            }
+
+                            // the iterators move the cards the same way
+                            let game = Game {
+                                cards,
+                                table,
+                                ..TEST_GAME
+                            };
+                            for new_game in game.forward() {
+                                let played = new_game.table;
+                                assert!(played == my1 || played == my2);
+                                assert_eq!(
+                                    new_game.cards,
+                                    rotate_hands(swap_card(cards, played, table))
+                                );
+                                assert!(new_game
+                                    .backward()
+                                    .any(|(prev_game, _)| prev_game == game));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // 5 of the 16 cards, two for each player and one for the table
+        assert_eq!(arrangements, 4368 * 30);
+    }
+}
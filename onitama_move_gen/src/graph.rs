@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::{gen::Game, moves::Move};
+
+/// A move from the position at index `from` to the position at index `to`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub mv: Move,
+}
+
+/// Positions reachable from a root, each stored once.
+/// Positions are identified by `Game::repetition_key`, so transpositions share a node.
+/// A position that repeats an earlier one adds an edge back to it, so the graph can have cycles.
+#[derive(Clone, Default, Debug)]
+pub struct PositionGraph {
+    games: Vec<Game>,
+    depths: Vec<u8>,
+    edges: Vec<Edge>,
+    index: HashMap<u128, usize>,
+}
+
+impl PositionGraph {
+    /// Positions in the order they were found, the root is at index 0.
+    pub fn nodes(&self) -> &[Game] {
+        &self.games
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Fewest plies from the root to the position at `index`.
+    pub fn depth(&self, index: usize) -> u8 {
+        self.depths[index]
+    }
+
+    pub fn index_of(&self, game: &Game) -> Option<usize> {
+        self.index.get(&game.repetition_key()).copied()
+    }
+
+    /// Indices of the positions where the game is over.
+    pub fn terminals(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.games.len()).filter(move |&i| self.games[i].is_loss())
+    }
+
+    /// Edges that leave the position at `index`, empty if it was not expanded.
+    pub fn edges_from(&self, index: usize) -> impl Iterator<Item = &Edge> + '_ {
+        self.edges.iter().filter(move |edge| edge.from == index)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    fn insert(&mut self, game: Game, depth: u8) -> (usize, bool) {
+        let len = self.games.len();
+        let index = *self.index.entry(game.repetition_key()).or_insert(len);
+        if index == len {
+            self.games.push(game);
+            self.depths.push(depth);
+        }
+        (index, index == len)
+    }
+}
+
+/// Builds the graph of positions up to `max_depth` plies from `root` breadth first.
+/// Positions at `max_depth` and finished games are not expanded.
+pub fn explore(root: &Game, max_depth: u8) -> PositionGraph {
+    let mut graph = PositionGraph::default();
+    graph.insert(*root, 0);
+    let mut frontier = vec![0];
+    for depth in 0..max_depth {
+        let mut next = Vec::new();
+        for from in frontier {
+            let game = graph.games[from];
+            if game.is_loss() {
+                continue;
+            }
+            for (mv, new_game) in game.forward_with_moves() {
+                let (to, new) = graph.insert(new_game, depth + 1);
+                if new {
+                    next.push(to);
+                }
+                graph.edges.push(Edge { from, to, mv });
+            }
+        }
+        frontier = next;
+    }
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::{perft, TEST_GAME};
+
+    #[test]
+    fn test_explore() {
+        let graph = explore(&TEST_GAME, 0);
+        assert_eq!(graph.len(), 1);
+        assert!(graph.edges().is_empty());
+
+        // the children of the start are all different
+        let moves = TEST_GAME.count_moves() as usize;
+        let graph = explore(&TEST_GAME, 1);
+        assert_eq!(graph.len(), 1 + moves);
+        assert_eq!(graph.edges().len(), moves);
+        assert_eq!(graph.edges_from(0).count(), moves);
+
+        // every line of perft ends in an edge, but lines can end in the same position
+        let lines = perft(TEST_GAME, 2) as usize;
+        let graph = explore(&TEST_GAME, 2);
+        assert_eq!(graph.edges().len(), moves + lines);
+        assert!(graph.len() <= 1 + moves + lines);
+        assert!(graph.len() > 1 + moves);
+
+        let graph = explore(&TEST_GAME, 3);
+        let lines = moves + lines + perft(TEST_GAME, 3) as usize;
+        let edges: u64 = (0..graph.len())
+            .filter(|&i| graph.depth(i) < 3 && !graph.nodes()[i].is_loss())
+            .map(|i| graph.nodes()[i].count_moves())
+            .sum();
+        assert_eq!(graph.edges().len() as u64, edges);
+        assert!(graph.edges().len() <= lines);
+        for edge in graph.edges() {
+            let from = graph.nodes()[edge.from];
+            assert_eq!(graph.index_of(&from.apply(edge.mv)), Some(edge.to));
+            assert!(graph.depth(edge.to) <= graph.depth(edge.from) + 1);
+        }
+        for i in graph.terminals() {
+            assert!(graph.nodes()[i].is_loss());
+            assert_eq!(graph.edges_from(i).count(), 0);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::gen::Game;
+
+impl Game {
+    /// Pieces of both players and both hands, which is everything that can repeat.
+    /// The table card is left out because it is the one of the five cards of the game
+    /// that is in neither hand, so it follows from the hands.
+    /// The fields are relative to the player to move like the rest of `Game`.
+    #[inline]
+    pub fn repetition_key(&self) -> u128 {
+        self.my as u128 | (self.other as u128) << 32 | (self.cards as u128) << 64
+    }
+}
+
+/// Repetition keys of the positions of one game, to detect cycles.
+#[derive(Clone, Default, Debug)]
+pub struct GameHistory {
+    keys: Vec<u128>,
+}
+
+impl GameHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn push(&mut self, game: &Game) {
+        self.keys.push(game.repetition_key());
+    }
+
+    #[inline]
+    pub fn pop(&mut self) {
+        self.keys.pop();
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// How often `game` already occurred.
+    pub fn count(&self, game: &Game) -> usize {
+        let key = game.repetition_key();
+        self.keys.iter().filter(|&&k| k == key).count()
+    }
+
+    #[inline]
+    pub fn is_repetition(&self, game: &Game) -> bool {
+        self.count(game) > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+
+    // one pawn each, my hand is tiger and boar, theirs is horse and elephant, ox is on the table
+    const GAME: Game = Game {
+        my: 1 << 0 | 1 << 5,
+        other: 1 << 0 | 1 << 10,
+        cards: (1 << 5 | 1 << 1) | (1 << 2 | 1 << 3) << 16,
+        table: 0,
+    };
+
+    fn play(moves: &[(u8, u8, u8)]) -> (Game, GameHistory) {
+        let mut game = GAME;
+        let mut history = GameHistory::new();
+        for &(from, to, card) in moves {
+            history.push(&game);
+            let mv = Move { from, to, card };
+            assert!(game.legal_moves_small().contains(&mv));
+            game = game.apply(mv);
+        }
+        (game, history)
+    }
+
+    #[test]
+    fn test_repetition() {
+        // both pawns go forward with tiger and back with ox and horse,
+        // which brings the cards back to where they started
+        let cycle = [
+            (5, 15, 5),
+            (10, 5, 2),
+            (15, 10, 0),
+            (5, 15, 5),
+            (10, 5, 2),
+            (15, 10, 0),
+        ];
+        let (game, history) = play(&cycle);
+        assert_eq!(game, GAME);
+        assert_eq!(history.len(), 6);
+        assert_eq!(history.count(&game), 1);
+        assert!(history.is_repetition(&game));
+
+        // the same start, but the last move goes sideways with elephant
+        let mut moves = cycle;
+        moves[5] = (15, 16, 3);
+        let (game, history) = play(&moves);
+        assert_ne!(game, GAME);
+        assert!(!history.is_repetition(&game));
+        assert_eq!(history.count(&GAME), 1);
+    }
+}
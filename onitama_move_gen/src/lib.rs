@@ -6,13 +6,34 @@
 #[macro_use]
 extern crate build_const;
 extern crate bitintr;
+extern crate fastrand;
 extern crate nudge;
 extern crate num_traits;
 
+pub mod attack;
+pub mod board;
+pub mod cache;
+pub mod card;
+pub mod deck;
 pub mod eval;
 pub mod gen;
+pub mod graph;
+pub mod history;
+pub mod moves;
 pub mod ops;
 pub mod perft;
+pub mod planes;
+pub mod record;
+pub mod see;
+pub mod selfplay;
+pub mod setup;
+pub mod state;
+pub mod symmetry;
 pub mod tablebase;
+pub mod tables;
+pub mod tree;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod zobrist;
 
 build_const!("lut");
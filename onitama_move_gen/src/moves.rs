@@ -0,0 +1,1049 @@
+use std::{fmt::Display, ops::Deref};
+
+use bitintr::{Andn, Popcnt};
+
+use crate::{
+    card::Card,
+    gen::{rotate_hands, swap_card, CaptureKind, Game, PIECE_MASK},
+    ops::BitIter,
+    SHIFTED, SHIFTED_L, SHIFTED_R, SHIFTED_U,
+};
+
+/// Result of `Game::status`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeStatus {
+    /// A move that takes the other king or brings my king to the temple.
+    Win(Move),
+    /// The player to move already lost.
+    Loss,
+    /// Neither, with the number of moves like `count_moves`.
+    Active(u64),
+}
+
+/// 5 pieces with 2 cards that have at most 4 destinations each.
+pub const MAX_MOVES: usize = 40;
+
+/// `from` and `to` are in the frame of the player making the move,
+/// `card` is the index of the card that is played.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub card: u8,
+}
+
+impl Move {
+    /// Packs `from` in bits 0-4, `to` in bits 5-9 and the absolute card index in bits 10-13.
+    #[inline]
+    pub fn to_u16(self) -> u16 {
+        self.from as u16 | (self.to as u16) << 5 | (self.card as u16) << 10
+    }
+
+    /// Inverse of `to_u16`, `None` if a square is off the board or unused bits are set.
+    #[inline]
+    pub fn from_u16(value: u16) -> Option<Move> {
+        let mv = Move {
+            from: (value & 0b11111) as u8,
+            to: (value >> 5 & 0b11111) as u8,
+            card: (value >> 10) as u8,
+        };
+        if mv.from < 25 && mv.to < 25 && mv.card < 16 {
+            Some(mv)
+        } else {
+            None
+        }
+    }
+
+    /// Parses the notation of `Display`, like `e1e2 ox`, or the same with the card first,
+    /// like `ox e1e2` in the `bestmove` of the engine protocol.
+    /// A `#` at the end, which `Game::forward_notated` adds to winning moves, is allowed.
+    pub fn from_notation(notation: &str) -> Option<Move> {
+        let notation = notation.trim();
+        let notation = notation.strip_suffix('#').unwrap_or(notation);
+        let (first, second) = notation.split_once(' ')?;
+        let (squares, card) = match Card::from_name(first) {
+            Ok(card) => (second, card),
+            Err(_) => (first, Card::from_name(second).ok()?),
+        };
+        let squares = squares.as_bytes();
+        if squares.len() != 4 {
+            return None;
+        }
+        Some(Move {
+            from: parse_square(&squares[..2])?,
+            to: parse_square(&squares[2..])?,
+            card: card.0 as u8,
+        })
+    }
+
+    /// The same move seen from the other side of the board.
+    /// The engine protocol prints the moves of red like this, in the frame of blue.
+    #[inline]
+    pub fn mirrored(self) -> Move {
+        Move {
+            from: 24 - self.from,
+            to: 24 - self.to,
+            card: self.card,
+        }
+    }
+}
+
+/// Columns are `e` to `a` and rows `1` to `5` like on the litama board of the engine protocol,
+/// so square 0 is `e1`, square 4 is `a1` and the temple 22 is `c5`.
+pub fn square_name(pos: u8) -> String {
+    let col = (b'e' - pos % 5) as char;
+    let row = (b'1' + pos / 5) as char;
+    [col, row].iter().collect()
+}
+
+fn parse_square(name: &[u8]) -> Option<u8> {
+    match name {
+        [col @ b'a'..=b'e', row @ b'1'..=b'5'] => Some((row - b'1') * 5 + b'e' - col),
+        _ => None,
+    }
+}
+
+/// Squares in the frame of the player making the move followed by the name of the card,
+/// like `e1e2 ox`.
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{} {}",
+            square_name(self.from),
+            square_name(self.to),
+            Card(self.card as u32)
+        )
+    }
+}
+
+/// Why `Game::apply_move_str` did not play a move.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    /// Not in the notation of `Move::from_notation`.
+    Notation(String),
+    /// Not legal in the position, or the game is already over.
+    Illegal(Move),
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::Notation(notation) => write!(f, "can not parse move: {}", notation),
+            MoveError::Illegal(mv) => write!(f, "illegal move: {}", mv),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// What happened in a move, see `apply_move_detailed`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveEffect {
+    pub captured: Option<CaptureKind>,
+    pub king_moved: bool,
+    /// The table card that the player took into their hand in exchange for `mv.card`.
+    pub card_swapped: Card,
+}
+
+/// A legal move with what it does, see `Game::annotated_moves`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AnnotatedMove {
+    pub mv: Move,
+    /// The game after the move, from the perspective of the other player.
+    pub child: Game,
+    /// Takes the other king or brings my king to the temple.
+    pub wins: bool,
+    pub capture: Option<CaptureKind>,
+    /// The other player can take my king after the move. Never set for a win.
+    pub king_exposed: bool,
+}
+
+/// Fixed capacity list of moves that lives on the stack.
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    #[inline]
+    pub fn new() -> Self {
+        MoveList {
+            moves: [Move::default(); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+/// Children of a game that is owned by the iterator, in the same order as `forward`.
+pub struct OwnedGameIter {
+    game: Game,
+    moves: MoveList,
+    index: usize,
+}
+
+impl Iterator for OwnedGameIter {
+    type Item = Game;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let &mv = self.moves.get(self.index)?;
+        self.index += 1;
+        Some(self.game.apply(mv))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.moves.len() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for OwnedGameIter {}
+
+impl Game {
+    #[inline]
+    pub fn into_children(self) -> OwnedGameIter {
+        OwnedGameIter {
+            moves: self.legal_moves_small(),
+            game: self,
+            index: 0,
+        }
+    }
+
+    /// Moves in the same order as `forward`.
+    #[inline]
+    pub fn legal_moves_small(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        for from in self.next_my() {
+            for card in self.next_my_card() {
+                for to in self.next_to(from, card) {
+                    moves.push(Move {
+                        from: from as u8,
+                        to: to as u8,
+                        card: card as u8,
+                    });
+                }
+            }
+        }
+        moves
+    }
+
+    pub fn legal_moves_vec(&self) -> Vec<Move> {
+        self.legal_moves_small().to_vec()
+    }
+
+    /// Children together with the move that leads to them, in the same order as `forward`.
+    pub fn forward_with_moves(&self) -> impl Iterator<Item = (Move, Game)> + '_ {
+        let moves = self.legal_moves_small();
+        (0..moves.len()).map(move |i| moves[i]).zip(self.forward())
+    }
+
+    /// Children with their moves sorted by `Move::to_u16`, so by card, then destination,
+    /// then origin. `forward` is just as stable but goes by origin first, this is the order
+    /// of the key that ties between moves are broken with, like in the search of the engine.
+    /// `RandomPolicy` picks its moves by index into this order.
+    pub fn forward_ordered(&self) -> Vec<(Move, Game)> {
+        let mut children: Vec<(Move, Game)> = self.forward_with_moves().collect();
+        children.sort_unstable_by_key(|&(mv, _)| mv.to_u16());
+        children
+    }
+
+    /// `forward_with_moves` with the moves in the notation of `Display`.
+    /// Moves that win by taking the other king or reaching the temple end in `#`.
+    pub fn forward_notated(&self) -> impl Iterator<Item = (String, Game)> + '_ {
+        self.forward_with_moves().map(|(mv, new_game)| {
+            let mut notation = mv.to_string();
+            if new_game.is_loss() {
+                notation.push('#');
+            }
+            (notation, new_game)
+        })
+    }
+
+    /// Parses a move with `Move::from_notation` and plays it if it is legal.
+    pub fn apply_move_str(&self, notation: &str) -> Result<Game, MoveError> {
+        let mv = Move::from_notation(notation)
+            .ok_or_else(|| MoveError::Notation(notation.to_string()))?;
+        if self.is_loss() || !self.legal_moves_small().contains(&mv) {
+            return Err(MoveError::Illegal(mv));
+        }
+        Ok(self.apply(mv))
+    }
+
+    /// The move at index `n` of `legal_moves_vec` without generating the moves before it.
+    pub fn nth_move(&self, mut n: usize) -> Option<Move> {
+        for from in self.next_my() {
+            for card in self.next_my_card() {
+                let to = self.my.andn(SHIFTED[card as usize][from as usize]);
+                let count = to.popcnt() as usize;
+                if n < count {
+                    let to = BitIter(to).nth(n).unwrap();
+                    return Some(Move {
+                        from: from as u8,
+                        to: to as u8,
+                        card: card as u8,
+                    });
+                }
+                n -= count;
+            }
+        }
+        None
+    }
+
+    /// `is_loss`, `is_win` and `count_moves` in one pass over the pieces.
+    /// The destinations of both cards are packed like in `count_moves`
+    /// and checked for a win before they are counted.
+    pub fn status(&self) -> NodeStatus {
+        if self.is_loss() {
+            return NodeStatus::Loss;
+        }
+        let my = self.my as u64 | (self.my as u64) << 32;
+        let other_king = 1 << 24 >> self.other.wrapping_shr(25);
+        let my_king = self.my.wrapping_shr(25);
+        let mut total = 0;
+        for from in self.next_my() {
+            let mut cards = self.next_my_card();
+            let (card1, card2) = (cards.next().unwrap(), cards.next().unwrap());
+            let both = my.andn(unsafe {
+                SHIFTED_L
+                    .get_unchecked(card1 as usize)
+                    .get_unchecked(from as usize)
+                    | SHIFTED_U
+                        .get_unchecked(card2 as usize)
+                        .get_unchecked(from as usize)
+            });
+            let target = if from == my_king {
+                other_king | 1 << 22
+            } else {
+                other_king
+            };
+            let wins = both & (target as u64 | (target as u64) << 32);
+            if wins != 0 {
+                let bit = wins.trailing_zeros();
+                let (card, to) = if bit < 32 {
+                    (card1, bit)
+                } else {
+                    (card2, bit - 32)
+                };
+                return NodeStatus::Win(Move {
+                    from: from as u8,
+                    to: to as u8,
+                    card: card as u8,
+                });
+            }
+            total += both.popcnt();
+        }
+        NodeStatus::Active(total)
+    }
+
+    /// Moves that take the other king or bring my king to the temple.
+    pub fn winning_moves(&self) -> Vec<Move> {
+        let my_king = self.my_king();
+        self.legal_moves_small()
+            .iter()
+            .copied()
+            .filter(|mv| {
+                (mv.from == my_king && mv.to == 22)
+                    || self.capture_target(mv.to) == Some(CaptureKind::King)
+            })
+            .collect()
+    }
+
+    /// Every legal move with whether it wins, what it takes and whether it leaves my king
+    /// to be taken, in the same order as `forward`.
+    pub fn annotated_moves(&self) -> Vec<AnnotatedMove> {
+        self.forward_with_moves()
+            .map(|(mv, child)| {
+                let wins = child.is_loss();
+                AnnotatedMove {
+                    mv,
+                    child,
+                    wins,
+                    capture: self.capture_target(mv.to),
+                    // my king is the other king of the child
+                    king_exposed: !wins && child.my_attackers_of(24 - child.other_king()) != 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Moves the other player could make if it were their turn that take a piece or win.
+    /// The moves are in the frame of the other player, like the moves of `null_move`.
+    pub fn threats(&self) -> Vec<Move> {
+        if self.is_loss() {
+            return Vec::new();
+        }
+        let game = self.null_move();
+        let king = game.my_king();
+        game.legal_moves_small()
+            .iter()
+            .copied()
+            .filter(|mv| (mv.from == king && mv.to == 22) || game.capture_target(mv.to).is_some())
+            .collect()
+    }
+
+    /// Moves of the piece on `from` with both cards, empty if it is not my piece.
+    pub fn moves_from(&self, from: u8) -> impl Iterator<Item = (Move, Game)> + '_ {
+        let cards = if from < 25 && self.my & PIECE_MASK & 1 << from != 0 {
+            self.cards & 0xffff
+        } else {
+            0
+        };
+        BitIter(cards).flat_map(move |card| {
+            self.next_to(from as u32, card).map(move |to| {
+                let mv = Move {
+                    from,
+                    to: to as u8,
+                    card: card as u8,
+                };
+                (mv, self.apply(mv))
+            })
+        })
+    }
+
+    /// Squares the piece on `from` can move to with either card, 0 if it is not my piece.
+    #[inline]
+    pub fn destinations_from(&self, from: u8) -> u32 {
+        if from >= 25 || self.my & PIECE_MASK & 1 << from == 0 {
+            return 0;
+        }
+        let mut destinations = 0;
+        for card in self.next_my_card() {
+            destinations |= SHIFTED[card as usize][from as usize];
+        }
+        self.my.andn(destinations)
+    }
+
+    /// Squares the piece on `from` can stand on after 1 to `plies` of my moves with only this piece.
+    /// My two cards are used for every move, the cards I would pick up are not followed.
+    /// Pieces of the other player never block since they are taken. With `blocked` my other
+    /// pieces stay where they are and can not be moved onto, otherwise the board is empty.
+    /// 0 if it is not my piece.
+    pub fn piece_reachable_in(&self, from: u8, plies: u8, blocked: bool) -> u32 {
+        if from >= 25 || self.my & PIECE_MASK & 1 << from == 0 {
+            return 0;
+        }
+        let blockers = if blocked {
+            self.my & PIECE_MASK & !(1 << from)
+        } else {
+            0
+        };
+        let (mut frontier, mut reach) = (1 << from, 0);
+        for _ in 0..plies {
+            let mut next = 0;
+            for pos in BitIter(frontier) {
+                for card in self.next_my_card() {
+                    next |= SHIFTED[card as usize][pos as usize];
+                }
+            }
+            let next = blockers.andn(next);
+            reach |= next;
+            // the squares after one more move only depend on the squares before it
+            if next == frontier {
+                break;
+            }
+            frontier = next;
+        }
+        reach
+    }
+
+    /// `count_moves` as if `add` replaced `remove` in my hand, without changing the game.
+    /// `None` if `remove` is not in my hand or `add` already is.
+    /// `add` may be any other card, also one that is on the table or with the other player.
+    pub fn count_moves_with_card_substituted(&self, remove: Card, add: Card) -> Option<u64> {
+        let hand = self.cards & 0xffff;
+        if hand & 1 << remove.0 == 0 || hand & 1 << add.0 != 0 {
+            return None;
+        }
+        let game = Game {
+            cards: self.cards ^ 1 << remove.0 ^ 1 << add.0,
+            ..*self
+        };
+        Some(game.count_moves())
+    }
+
+    /// Square of each of my pieces with the number of squares it can move to.
+    /// A square that both cards reach is counted once, unlike in `count_moves`.
+    pub fn mobility_by_piece(&self) -> Vec<(u32, u8)> {
+        BitIter(self.my & PIECE_MASK)
+            .map(|from| (from, self.destinations_from(from as u8).popcnt() as u8))
+            .collect()
+    }
+
+    /// Moves with destination `to`, found with the reverse tables.
+    /// The order is the same as in `legal_moves_vec`. Empty if `to` is off the board.
+    pub fn moves_reaching(&self, to: u8) -> Vec<Move> {
+        if to >= 25 {
+            return Vec::new();
+        }
+        let mut moves = Vec::new();
+        if self.my & 1 << to != 0 {
+            return moves;
+        }
+        let reach = |card: u32| SHIFTED_R[card as usize][to as usize];
+        let sources = self.next_my_card().fold(0, |acc, card| acc | reach(card));
+        for from in BitIter(sources & self.my & PIECE_MASK) {
+            for card in self.next_my_card() {
+                if reach(card) & 1 << from != 0 {
+                    moves.push(Move {
+                        from: from as u8,
+                        to,
+                        card: card as u8,
+                    });
+                }
+            }
+        }
+        moves
+    }
+
+    #[inline]
+    pub fn apply(&self, mv: Move) -> Game {
+        self.step(
+            mv.from as u32,
+            mv.card as u32,
+            mv.to as u32,
+            self.my.wrapping_shr(25),
+        )
+    }
+
+    #[inline]
+    pub fn apply_move_detailed(&self, mv: Move) -> (Game, MoveEffect) {
+        let effect = MoveEffect {
+            captured: self.capture_target(mv.to),
+            king_moved: mv.from as u32 == self.my.wrapping_shr(25),
+            card_swapped: Card(self.table),
+        };
+        (self.apply(mv), effect)
+    }
+
+    /// The game before `mv` was played, where `self` is the result of `apply_move_detailed`.
+    /// The effect has the piece that was taken and the card that went back to the table.
+    #[inline]
+    pub fn undo_move(&self, mv: Move, effect: MoveEffect) -> Game {
+        let (from, to) = (mv.from as u32, mv.to as u32);
+        let mut my = self.other ^ (1 << from) ^ (1 << to);
+        if effect.king_moved {
+            my = my & PIECE_MASK | from << 25;
+        }
+        let mut other = self.my;
+        if effect.captured.is_some() {
+            other |= 1 << 24 >> to;
+        }
+        Game {
+            my,
+            other,
+            cards: swap_card(
+                rotate_hands(self.cards),
+                effect.card_swapped.0,
+                mv.card as u32,
+            ),
+            table: effect.card_swapped.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+    use crate::{perft::TEST_GAME, setup::random_games};
+
+    fn check_moves(game: Game, depth: u8) {
+        let moves = game.legal_moves_small();
+        assert_eq!(moves.len() as u64, game.count_moves());
+        assert_eq!(moves.to_vec(), game.legal_moves_vec());
+        for (&mv, new_game) in moves.iter().zip(game.forward()) {
+            assert_eq!(game.apply(mv), new_game);
+            if depth > 1 && !new_game.is_loss() {
+                check_moves(new_game, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        check_moves(TEST_GAME, 4);
+    }
+
+    fn check_children(game: Game, depth: u8) {
+        let mut iter = game.into_children();
+        let children: Vec<Game> = game.forward().collect();
+        assert_eq!(iter.len(), children.len());
+        assert_eq!(iter.next(), children.first().copied());
+        assert_eq!(iter.len(), children.len() - 1);
+        assert!(iter.eq(children.iter().copied().skip(1)));
+        if depth > 1 {
+            for new_game in children.into_iter().filter(|g| !g.is_loss()) {
+                check_children(new_game, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_children() {
+        check_children(TEST_GAME, 3);
+    }
+
+    #[test]
+    fn test_apply_move_detailed() {
+        for &mv in TEST_GAME.legal_moves_small().iter() {
+            let (new_game, effect) = TEST_GAME.apply_move_detailed(mv);
+            assert_eq!(new_game, TEST_GAME.apply(mv));
+            assert_eq!(effect.captured, None);
+            assert_eq!(effect.king_moved, mv.from == 2);
+            assert_eq!(effect.card_swapped, Card(TEST_GAME.table));
+        }
+
+        // the king on 12 can take the other king on 17 with ox
+        let game = Game {
+            my: 1 << 12 | 1 << 15 | 12 << 25,
+            other: 1 << 7 | 1 << 4 | 7 << 25,
+            ..TEST_GAME
+        };
+        let mv = Move {
+            from: 12,
+            to: 17,
+            card: 0,
+        };
+        let (new_game, effect) = game.apply_move_detailed(mv);
+        assert!(new_game.is_loss());
+        assert_eq!(
+            effect,
+            MoveEffect {
+                captured: Some(CaptureKind::King),
+                king_moved: true,
+                card_swapped: Card(4),
+            }
+        );
+        // the pawn on 15 can take the other pawn on 20 with ox
+        let (_, effect) = game.apply_move_detailed(Move {
+            from: 15,
+            to: 20,
+            card: 0,
+        });
+        assert_eq!(effect.captured, Some(CaptureKind::Pawn));
+        assert!(!effect.king_moved);
+    }
+
+    #[test]
+    fn test_moves_reaching() {
+        let rng = Rng::with_seed(3);
+        for game in random_games(&rng, 1000) {
+            let moves = game.legal_moves_vec();
+            for to in 0..32 {
+                let reaching: Vec<Move> = moves.iter().copied().filter(|mv| mv.to == to).collect();
+                assert_eq!(game.moves_reaching(to), reaching);
+            }
+        }
+    }
+
+    #[test]
+    fn test_moves_from() {
+        let rng = Rng::with_seed(4);
+        for game in random_games(&rng, 1000) {
+            let moves = game.legal_moves_vec();
+            for from in 0..32 {
+                let expected: Vec<(Move, Game)> = moves
+                    .iter()
+                    .filter(|mv| mv.from == from)
+                    .map(|&mv| (mv, game.apply(mv)))
+                    .collect();
+                assert_eq!(game.moves_from(from).collect::<Vec<_>>(), expected);
+                let destinations = expected.iter().fold(0, |acc, (mv, _)| acc | 1 << mv.to);
+                assert_eq!(game.destinations_from(from), destinations);
+            }
+        }
+    }
+
+    #[test]
+    fn test_piece_reachable_in() {
+        let rng = Rng::with_seed(19);
+        for game in random_games(&rng, 1000) {
+            for from in 0..25 {
+                assert_eq!(
+                    game.piece_reachable_in(from, 1, true),
+                    game.destinations_from(from)
+                );
+                // two plies by playing a move and keeping the cards
+                let mut two = game.piece_reachable_in(from, 1, true);
+                for pos in BitIter(game.piece_reachable_in(from, 1, true)) {
+                    let moved = Game {
+                        my: game.my ^ 1 << from ^ 1 << pos,
+                        ..game
+                    };
+                    two |= moved.destinations_from(pos as u8);
+                }
+                assert_eq!(game.piece_reachable_in(from, 2, true), two);
+                assert_eq!(game.piece_reachable_in(from, 0, true), 0);
+            }
+        }
+
+        // a lone king with ox and boar reaches every square on an empty board
+        let king = Game {
+            my: 1 << 2 | 2 << 25,
+            ..TEST_GAME
+        };
+        assert_eq!(
+            king.piece_reachable_in(2, 1, false),
+            1 << 1 | 1 << 3 | 1 << 7
+        );
+        assert_eq!(king.piece_reachable_in(2, 8, false), PIECE_MASK);
+        assert_eq!(
+            TEST_GAME.piece_reachable_in(2, 8, false),
+            king.piece_reachable_in(2, 8, false)
+        );
+        // my pawns on the first row keep the king in front of them
+        assert_eq!(
+            TEST_GAME.piece_reachable_in(2, 8, true),
+            PIECE_MASK & !0b11011
+        );
+        assert_eq!(TEST_GAME.piece_reachable_in(5, 1, false), 0);
+    }
+
+    #[test]
+    fn test_count_moves_with_card_substituted() {
+        let (ox, boar, tiger) = (Card(0), Card(1), Card(5));
+        // tiger only moves forward by two from the first row
+        assert_eq!(
+            TEST_GAME.count_moves_with_card_substituted(ox, tiger),
+            Some(10)
+        );
+        assert_eq!(TEST_GAME.count_moves_with_card_substituted(tiger, ox), None);
+        assert_eq!(TEST_GAME.count_moves_with_card_substituted(ox, boar), None);
+
+        let rng = Rng::with_seed(11);
+        for game in random_games(&rng, 1000) {
+            let hand: Vec<u32> = BitIter(game.cards & 0xffff).collect();
+            let add = rng.u32(0..16);
+            if hand.contains(&add) {
+                continue;
+            }
+            let remove = hand[rng.usize(0..2)];
+            let substituted = Game {
+                cards: 1 << (hand[0] + hand[1] - remove) | 1 << add | game.cards & !0xffff,
+                ..game
+            };
+            assert_eq!(
+                game.count_moves_with_card_substituted(Card(remove), Card(add)),
+                Some(substituted.count_moves())
+            );
+            assert_eq!(
+                substituted.count_moves(),
+                substituted.forward().count() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn test_mobility_by_piece() {
+        // ox and boar only move the pieces forward from the first row, with both cards
+        let mobility = TEST_GAME.mobility_by_piece();
+        assert_eq!(mobility, vec![(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)]);
+        assert_eq!(TEST_GAME.count_moves(), 10);
+
+        let rng = Rng::with_seed(7);
+        for game in random_games(&rng, 1000) {
+            let mobility = game.mobility_by_piece();
+            assert_eq!(mobility.len(), game.count_pieces());
+            let mut pairs: Vec<(u8, u8)> = game
+                .legal_moves_vec()
+                .iter()
+                .map(|mv| (mv.from, mv.to))
+                .collect();
+            pairs.sort_unstable();
+            pairs.dedup();
+            let total: u64 = mobility.iter().map(|&(_, count)| count as u64).sum();
+            assert_eq!(total, pairs.len() as u64);
+            // each destination is reached with one or both cards
+            assert!(total <= game.count_moves() && game.count_moves() <= 2 * total);
+        }
+    }
+
+    #[test]
+    fn test_move_order() {
+        let moves = TEST_GAME.legal_moves_vec();
+        let first = [(0, 5, 0), (0, 5, 1), (1, 6, 0), (1, 6, 1)];
+        for (mv, &(from, to, card)) in moves.iter().zip(first.iter()) {
+            assert_eq!(*mv, Move { from, to, card });
+        }
+
+        let rng = Rng::with_seed(9);
+        for game in random_games(&rng, 1000) {
+            let moves = game.legal_moves_vec();
+            let keys: Vec<_> = moves.iter().map(|mv| (mv.from, mv.card, mv.to)).collect();
+            assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(moves.iter().map(|&mv| game.apply(mv)).eq(game.forward()));
+        }
+    }
+
+    #[test]
+    fn test_undo_move() {
+        let rng = Rng::with_seed(15);
+        let mut captures = 0;
+        for game in random_games(&rng, 1000) {
+            for &mv in game.legal_moves_small().iter() {
+                let (new_game, effect) = game.apply_move_detailed(mv);
+                assert_eq!(new_game.undo_move(mv, effect), game);
+                captures += effect.captured.is_some() as usize;
+            }
+        }
+        assert!(captures > 0);
+    }
+
+    #[test]
+    fn test_notation() {
+        let mv = Move {
+            from: 0,
+            to: 5,
+            card: 0,
+        };
+        assert_eq!(mv.to_string(), "e1e2 ox");
+        assert_eq!(Move::from_notation(" e1e2 ox "), Some(mv));
+        assert_eq!(Move::from_notation("e1e2 lion"), None);
+        assert_eq!(Move::from_notation("e1f2 ox"), None);
+        assert_eq!(Move::from_notation("a1a2"), None);
+        assert_eq!(Move::from_notation("ox e1e2"), Some(mv));
+        assert_eq!(Move::from_notation("ox lion"), None);
+        assert_eq!(mv.mirrored().to_string(), "a5a4 ox");
+        assert_eq!(mv.mirrored().mirrored(), mv);
+
+        let rng = Rng::with_seed(10);
+        let mut wins = 0;
+        for game in random_games(&rng, 1000) {
+            assert_eq!(game.forward_notated().count() as u64, game.count_moves());
+            for (notation, new_game) in game.forward_notated() {
+                let mv = Move::from_notation(&notation).unwrap();
+                assert_eq!(game.apply(mv), new_game);
+                assert_eq!(notation.ends_with('#'), new_game.is_loss());
+                wins += new_game.is_loss() as usize;
+            }
+        }
+        assert!(wins > 0);
+    }
+
+    #[test]
+    fn test_apply_move_str() {
+        let mut game = TEST_GAME;
+        let mut expected = TEST_GAME;
+        for notation in &["e1e2 ox", "e1e2 horse", "d1d2 boar", "e2d2 elephant"] {
+            game = game.apply_move_str(notation).unwrap();
+            expected = expected.apply(Move::from_notation(notation).unwrap());
+            assert_eq!(game, expected);
+        }
+        // ox went back to the table and the other player took it
+        assert_eq!(
+            game.apply_move_str("c1c2 ox"),
+            Err(MoveError::Illegal(Move {
+                from: 2,
+                to: 7,
+                card: 0
+            }))
+        );
+        assert_eq!(
+            game.apply_move_str("c1 crab"),
+            Err(MoveError::Notation("c1 crab".to_string()))
+        );
+        assert!(game.apply_move_str("c1c2 crab").is_ok());
+    }
+
+    #[test]
+    fn test_annotated_moves() {
+        let rng = Rng::with_seed(20);
+        let (mut wins, mut exposed) = (0, 0);
+        for game in random_games(&rng, 1000) {
+            let annotated = game.annotated_moves();
+            assert_eq!(annotated.len() as u64, game.count_moves());
+            let winning = game.winning_moves();
+            for (a, (mv, child)) in annotated.iter().zip(game.forward_with_moves()) {
+                assert_eq!((a.mv, a.child), (mv, child));
+                assert_eq!(a.wins, winning.contains(&mv));
+                assert_eq!(a.capture, game.capture_target(mv.to));
+                let takes_king = !a.wins
+                    && child.forward().any(|new_game| {
+                        new_game.is_loss()
+                            && new_game.my & PIECE_MASK & 1 << new_game.my_king() == 0
+                    });
+                assert_eq!(a.king_exposed, takes_king);
+                wins += a.wins as usize;
+                exposed += a.king_exposed as usize;
+            }
+        }
+        assert!(wins > 0 && exposed > 0);
+    }
+
+    #[test]
+    fn test_threats() {
+        assert!(TEST_GAME.threats().is_empty());
+        // the other player threatens to take my king on 17 with tiger from 7
+        let game = Game {
+            my: 1 << 7 | 7 << 25,
+            other: 1 << 2 | 1 << 7 | 2 << 25,
+            cards: (1 << 0 | 1 << 4) | (1 << 5 | 1 << 2) << 16,
+            table: 14,
+        };
+        let threats = game.threats();
+        assert!(threats.contains(&Move {
+            from: 7,
+            to: 17,
+            card: 5
+        }));
+        assert!(game.null_move().apply(threats[0]).is_loss());
+
+        let rng = Rng::with_seed(19);
+        for game in random_games(&rng, 1000) {
+            let threats = game.threats();
+            let null = game.null_move();
+            for mv in &threats {
+                assert!(null.capture_target(mv.to).is_some() || null.apply(*mv).is_loss());
+            }
+            let winning = null.winning_moves();
+            assert!(winning.iter().all(|mv| threats.contains(mv)));
+        }
+    }
+
+    #[test]
+    fn test_forward_ordered() {
+        let ordered = TEST_GAME.forward_ordered();
+        assert_eq!(ordered[0].0, Move::from_notation("e1e2 ox").unwrap());
+        assert_eq!(ordered[9].0, Move::from_notation("a1a2 boar").unwrap());
+
+        let rng = Rng::with_seed(22);
+        for game in random_games(&rng, 1000) {
+            let ordered = game.forward_ordered();
+            assert!(ordered
+                .windows(2)
+                .all(|w| w[0].0.to_u16() < w[1].0.to_u16()));
+            let mut children: Vec<(Move, Game)> = game.forward_with_moves().collect();
+            children.sort_by_key(|&(mv, _)| mv.to_u16());
+            assert_eq!(ordered, children);
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        assert_eq!(TEST_GAME.status(), NodeStatus::Active(10));
+
+        let rng = Rng::with_seed(8);
+        let mut wins = 0;
+        for game in random_games(&rng, 1000) {
+            match game.status() {
+                NodeStatus::Win(mv) => {
+                    assert!(game.winning_moves().contains(&mv));
+                    wins += 1;
+                }
+                NodeStatus::Active(count) => {
+                    assert!(game.winning_moves().is_empty());
+                    assert_eq!(count, game.count_moves());
+                }
+                NodeStatus::Loss => unreachable!(),
+            }
+            for new_game in game.forward() {
+                if new_game.is_loss() {
+                    assert_eq!(new_game.status(), NodeStatus::Loss);
+                }
+            }
+        }
+        assert!(wins > 0);
+    }
+
+    #[test]
+    fn test_move_u16() {
+        let rng = Rng::with_seed(5);
+        for game in random_games(&rng, 1000) {
+            let moves = game.legal_moves_vec();
+            for &mv in moves.iter() {
+                let decoded = Move::from_u16(mv.to_u16()).unwrap();
+                assert_eq!(decoded, mv);
+                assert!(moves.contains(&decoded));
+            }
+        }
+        assert_eq!(Move::from_u16(25), None);
+        assert_eq!(Move::from_u16(25 << 5), None);
+        assert_eq!(Move::from_u16(1 << 14), None);
+        assert_eq!(
+            Move::from_u16(3 | 8 << 5 | 15 << 10),
+            Some(Move {
+                from: 3,
+                to: 8,
+                card: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_winning_moves() {
+        assert!(TEST_GAME.winning_moves().is_empty());
+
+        // the king on 17 can go to the temple and both the pawn on 13
+        // and the king can take the other king on 18
+        let game = Game {
+            my: 1 << 17 | 1 << 13 | 1 << 0 | 17 << 25,
+            other: 1 << 6 | 1 << 1 | 6 << 25,
+            ..TEST_GAME
+        };
+        assert!(game.is_win());
+        let moves = game.winning_moves();
+        let expected = [
+            (13, 18, 0),
+            (13, 18, 1),
+            (17, 22, 0),
+            (17, 18, 1),
+            (17, 22, 1),
+        ];
+        assert_eq!(moves.len(), expected.len());
+        for (mv, &(from, to, card)) in moves.iter().zip(expected.iter()) {
+            assert_eq!(*mv, Move { from, to, card });
+        }
+        // every other move does not end the game
+        let losses = game.forward().filter(Game::is_loss).count();
+        assert_eq!(losses, moves.len());
+        assert!(game.count_moves() as usize > losses);
+    }
+
+    #[test]
+    fn test_move_access() {
+        let rng = Rng::with_seed(7);
+        for game in random_games(&rng, 1000) {
+            let with_moves: Vec<(Move, Game)> = game.forward_with_moves().collect();
+            assert_eq!(with_moves.len(), game.count_moves() as usize);
+            for (n, &(mv, new_game)) in with_moves.iter().enumerate() {
+                assert_eq!(game.nth_move(n), Some(mv));
+                assert_eq!(game.apply(mv), new_game);
+            }
+            assert_eq!(game.nth_move(with_moves.len()), None);
+        }
+    }
+
+    #[test]
+    fn test_max_moves() {
+        for card in SHIFTED.iter() {
+            for &shifted in card.iter() {
+                assert!(shifted.count_ones() as usize * 10 <= MAX_MOVES);
+            }
+        }
+    }
+}
@@ -47,6 +47,22 @@ impl Iterator for CardIter {
     }
 }
 
+/// The splitmix64 generator, for random numbers that have to be the same
+/// on every machine and with every version of this crate, which `fastrand` does not promise.
+#[derive(Clone, Debug)]
+pub(crate) struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    #[inline]
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ z >> 30).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ z >> 27).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ z >> 31
+    }
+}
+
 // #[inline]
 // pub fn shift_or(card: &[u32; 25], pieces: u32) -> u32 {
 //     let mut result = 0;
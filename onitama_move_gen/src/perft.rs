@@ -1,7 +1,16 @@
-use crate::gen::Game;
+use std::collections::HashMap;
 
+use crate::{gen::Game, zobrist::ZobristKeys};
+
+/// Number of games `depth` plies below `game`, a finished game counts where it ends.
 #[inline(never)]
-fn perft(game: Game, depth: u8) -> u64 {
+pub fn perft(game: Game, depth: u8) -> u64 {
+    // the recursion stops at depth 2, these are only reached from the outside
+    match depth {
+        0 => return 1,
+        1 => return game.count_moves(),
+        _ => {}
+    }
     let mut total = 0;
     for new_game in game.forward() {
         if new_game.is_loss() {
@@ -15,29 +24,179 @@ fn perft(game: Game, depth: u8) -> u64 {
     total
 }
 
-pub fn perft_test(depth: u8) -> u64 {
-    const TEST_GAME: Game = Game {
-        my: 0b11111 | 2 << 25,
-        other: 0b11111 | 2 << 25,
-        cards: 0b00011 | 0b01100 << 16,
-        table: 4,
-    };
+/// `perft` at every depth from 1 to `depth` in one traversal, the count at depth `i + 1` at index `i`.
+/// A finished game counts at its own depth and every depth after it, like in `perft`.
+pub fn perft_breakdown(game: Game, depth: u8) -> Vec<u64> {
+    fn walk(game: Game, ply: usize, counts: &mut [u64]) {
+        for new_game in game.forward() {
+            if new_game.is_loss() {
+                counts[ply..].iter_mut().for_each(|count| *count += 1);
+            } else {
+                counts[ply] += 1;
+                if ply + 1 < counts.len() {
+                    walk(new_game, ply + 1, counts);
+                }
+            }
+        }
+    }
+    let mut counts = vec![0; depth as usize];
+    if depth > 0 {
+        walk(game, 0, &mut counts);
+    }
+    counts
+}
+
+pub const TEST_GAME: Game = Game {
+    my: 0b11111 | 2 << 25,
+    other: 0b11111 | 2 << 25,
+    cards: 0b00011 | 0b01100 << 16,
+    table: 4,
+};
 
+pub fn perft_test(depth: u8) -> u64 {
     perft(TEST_GAME, depth)
 }
 
+impl Game {
+    /// Same as `perft`, but subtree counts are cached by zobrist hash and depth.
+    pub fn perft_hashed(
+        &self,
+        keys: &ZobristKeys,
+        depth: u8,
+        cache: &mut HashMap<(u64, u8), u64>,
+    ) -> u64 {
+        perft_hashed(*self, self.zobrist_hash(keys), keys, depth, cache)
+    }
+}
+
+fn perft_hashed(
+    game: Game,
+    hash: u64,
+    keys: &ZobristKeys,
+    depth: u8,
+    cache: &mut HashMap<(u64, u8), u64>,
+) -> u64 {
+    match depth {
+        0 => return 1,
+        1 => return game.count_moves(),
+        _ => {}
+    }
+    if let Some(&total) = cache.get(&(hash, depth)) {
+        return total;
+    }
+    let mut total = 0;
+    for (new_game, new_hash) in game.children_with_hash(keys, hash) {
+        if new_game.is_loss() {
+            total += 1;
+        } else if depth == 2 {
+            total += new_game.count_moves();
+        } else {
+            total += perft_hashed(new_game, new_hash, keys, depth - 1, cache);
+        }
+    }
+    cache.insert((hash, depth), total);
+    total
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SuiteResult {
+    pub game: Game,
+    pub depth: u32,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl SuiteResult {
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.expected == self.actual
+    }
+}
+
+/// Runs `perft` on every `(game, depth, expected)` and reports the actual counts.
+/// With `progress` every result is printed to stderr as soon as it is known.
+pub fn perft_suite(positions: &[(Game, u32, u64)], progress: bool) -> Vec<SuiteResult> {
+    let mut results = Vec::with_capacity(positions.len());
+    for (i, &(game, depth, expected)) in positions.iter().enumerate() {
+        let actual = perft(game, depth as u8);
+        let result = SuiteResult {
+            game,
+            depth,
+            expected,
+            actual,
+        };
+        if progress {
+            eprintln!(
+                "{}/{} depth {}: {} {}",
+                i + 1,
+                positions.len(),
+                depth,
+                actual,
+                if result.passed() { "ok" } else { "FAILED" }
+            );
+        }
+        results.push(result);
+    }
+    results
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_perft() {
-        // assert_eq!(perft_test(0), 1);
-        // assert_eq!(perft_test(1), 10);
+        assert_eq!(perft_test(0), 1);
+        assert_eq!(perft_test(1), 10);
         assert_eq!(perft_test(2), 130);
         assert_eq!(perft_test(3), 1989);
         assert_eq!(perft_test(4), 28509);
         assert_eq!(perft_test(5), 487780);
         assert_eq!(perft_test(6), 7748422);
     }
+
+    #[test]
+    fn test_perft_breakdown() {
+        assert!(perft_breakdown(TEST_GAME, 0).is_empty());
+        assert_eq!(
+            perft_breakdown(TEST_GAME, 5),
+            vec![10, 130, 1989, 28509, 487780]
+        );
+        let game = Game::standard_opening("tiger,crab,horse,mantis,frog").unwrap();
+        let counts = perft_breakdown(game, 5);
+        assert_eq!(counts[0], game.count_moves());
+        for depth in 2..=5 {
+            assert_eq!(counts[depth as usize - 1], perft(game, depth));
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        let keys = ZobristKeys::new(0);
+        let mut cache = HashMap::new();
+        for depth in 0..=6 {
+            assert_eq!(
+                TEST_GAME.perft_hashed(&keys, depth, &mut cache),
+                perft_test(depth)
+            );
+        }
+        let game = Game::standard_opening("tiger,crab,horse,mantis,frog").unwrap();
+        let mut cache = HashMap::new();
+        assert_eq!(game.perft_hashed(&keys, 5, &mut cache), perft(game, 5));
+    }
+
+    #[test]
+    fn test_perft_suite() {
+        let positions = [
+            (TEST_GAME, 0, 1),
+            (TEST_GAME, 1, 10),
+            (TEST_GAME, 3, 1989),
+            (TEST_GAME, 4, 28508),
+        ];
+        let results = perft_suite(&positions, false);
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(SuiteResult::passed));
+        assert!(!results[3].passed());
+        assert_eq!(results[3].actual, 28509);
+    }
 }
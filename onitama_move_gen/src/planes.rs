@@ -0,0 +1,167 @@
+use crate::{
+    card::Color,
+    gen::{Game, PIECE_MASK},
+    ops::BitIter,
+};
+
+pub const PLANES: usize = 4;
+
+pub type Planes = [[f32; 25]; PLANES];
+
+/// Piece planes followed by one-hot card indices, see `ml_features`.
+pub const ML_FEATURES: usize = PLANES * 25 + 3 * 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Orientation {
+    /// Planes are my pawns, my king, other pawns, other king,
+    /// seen from the player to move.
+    SideToMove,
+    /// Planes are blue pawns, blue king, red pawns, red king,
+    /// always seen from blue. `to_move` is the color of the player to move.
+    Fixed { to_move: Color },
+}
+
+impl Game {
+    pub fn as_planes(&self, orientation: Orientation) -> Planes {
+        let mut planes = [[0.; 25]; PLANES];
+        let flip = orientation
+            == Orientation::Fixed {
+                to_move: Color::Red,
+            };
+        let (near, far) = if flip { (2, 0) } else { (0, 2) };
+
+        let my_king = self.my.wrapping_shr(25);
+        for pos in BitIter(self.my & PIECE_MASK & !(1 << my_king)) {
+            planes[near][frame(pos, flip)] = 1.;
+        }
+        if self.my & 1 << my_king != 0 {
+            planes[near + 1][frame(my_king, flip)] = 1.;
+        }
+
+        let other_king = self.other.wrapping_shr(25);
+        for pos in BitIter(self.other & PIECE_MASK & !(1 << other_king)) {
+            planes[far][frame(pos, !flip)] = 1.;
+        }
+        if self.other & 1 << other_king != 0 {
+            planes[far + 1][frame(other_king, !flip)] = 1.;
+        }
+        planes
+    }
+
+    /// Fixed layout input for training, always from the view of the player to move.
+    /// - `0..100`: planes of my pawns, my king, other pawns and other king,
+    ///   25 squares each in my frame, as in `Orientation::SideToMove`.
+    /// - `100..116`: my two cards by card index.
+    /// - `116..132`: the two cards of the other player.
+    /// - `132..148`: the table card.
+    pub fn ml_features(&self) -> [f32; ML_FEATURES] {
+        let mut features = [0.; ML_FEATURES];
+        let planes = self.as_planes(Orientation::SideToMove);
+        for (i, plane) in planes.iter().enumerate() {
+            features[i * 25..(i + 1) * 25].copy_from_slice(plane);
+        }
+        let cards = PLANES * 25;
+        for card in BitIter(self.cards & 0xffff) {
+            features[cards + card as usize] = 1.;
+        }
+        for card in BitIter(self.cards.wrapping_shr(16)) {
+            features[cards + 16 + card as usize] = 1.;
+        }
+        features[cards + 32 + self.table as usize] = 1.;
+        features
+    }
+}
+
+/// `pos` is in the frame of its owner, `flip` rotates it to the frame of their opponent.
+#[inline]
+fn frame(pos: u32, flip: bool) -> usize {
+    if flip {
+        24 - pos as usize
+    } else {
+        pos as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    fn pass(game: &Game) -> Game {
+        Game {
+            my: game.other,
+            other: game.my,
+            cards: game.cards.rotate_left(16),
+            table: game.table,
+        }
+    }
+
+    fn rotate(plane: [f32; 25]) -> [f32; 25] {
+        let mut res = plane;
+        res.reverse();
+        res
+    }
+
+    fn games() -> Vec<Game> {
+        let mut games = vec![TEST_GAME];
+        for new_game in TEST_GAME.forward() {
+            games.extend(new_game.forward());
+        }
+        games
+    }
+
+    #[test]
+    fn test_side_to_move() {
+        for game in games() {
+            let planes = game.as_planes(Orientation::SideToMove);
+            let total: f32 = planes.iter().flatten().sum();
+            let pieces =
+                (game.my & PIECE_MASK).count_ones() + (game.other & PIECE_MASK).count_ones();
+            assert_eq!(total, pieces as f32);
+
+            let passed = pass(&game).as_planes(Orientation::SideToMove);
+            assert_eq!(passed[0], rotate(planes[2]));
+            assert_eq!(passed[1], rotate(planes[3]));
+            assert_eq!(passed[2], rotate(planes[0]));
+            assert_eq!(passed[3], rotate(planes[1]));
+        }
+    }
+
+    #[test]
+    fn test_fixed() {
+        for game in games() {
+            let blue = game.as_planes(Orientation::Fixed {
+                to_move: Color::Blue,
+            });
+            assert_eq!(blue, game.as_planes(Orientation::SideToMove));
+            // the same position with red to move after a pass
+            let red = pass(&game).as_planes(Orientation::Fixed {
+                to_move: Color::Red,
+            });
+            assert_eq!(blue, red);
+        }
+    }
+
+    #[test]
+    fn test_ml_features() {
+        let features = TEST_GAME.ml_features();
+        // pawns on 0, 1, 3, 4 and the king on 2, the same for the other player
+        assert_eq!(features[..5], [1., 1., 0., 1., 1.]);
+        assert_eq!(features[25 + 2], 1.);
+        assert_eq!(features[50 + 20..75], [1., 1., 0., 1., 1.]);
+        assert_eq!(features[75 + 22], 1.);
+        // ox and boar, horse and elephant, crab
+        assert_eq!(features[100..102], [1., 1.]);
+        assert_eq!(features[116 + 2..116 + 4], [1., 1.]);
+        assert_eq!(features[132 + 4], 1.);
+        assert_eq!(features.iter().sum::<f32>(), 15.);
+
+        for game in games() {
+            let features = game.ml_features();
+            let planes = game.as_planes(Orientation::SideToMove);
+            assert_eq!(features[..100], planes.concat()[..]);
+            let cards: f32 = features[100..].iter().sum();
+            assert_eq!(cards, 5.);
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt::Display;
+
+use crate::{
+    card::{Card, CardError, Color},
+    gen::Game,
+    moves::Move,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RecordError {
+    /// A header line is missing or malformed.
+    Header(String),
+    /// The first player does not match the color of the table card.
+    FirstPlayer,
+    /// A move line that can not be parsed, with its number.
+    Notation(usize, String),
+    /// A move that is not legal in the position it is played in, with its number.
+    Illegal(usize, Move),
+    /// A move tagged with the color that is not to move, with its number.
+    Turn(usize, Color),
+    Cards(CardError),
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Header(line) => write!(f, "malformed header: {}", line),
+            RecordError::FirstPlayer => {
+                f.write_str("the first player does not have the color of the table card")
+            }
+            RecordError::Notation(number, line) => {
+                write!(f, "move {} can not be parsed: {}", number, line)
+            }
+            RecordError::Illegal(number, mv) => write!(f, "move {} is not legal: {}", number, mv),
+            RecordError::Turn(number, color) => {
+                write!(f, "move {} is played by {} out of turn", number, color)
+            }
+            RecordError::Cards(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<CardError> for RecordError {
+    fn from(err: CardError) -> Self {
+        RecordError::Cards(err)
+    }
+}
+
+/// A full game as the five cards of `Game::start_with` and the moves played.
+/// The text format has a header with the cards and the first player
+/// followed by one numbered move per line in the notation of `Move`:
+/// ```text
+/// cards: ox boar horse elephant crab
+/// first: blue
+/// 1. e1e2 ox
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct GameRecord {
+    /// `[blue, blue, red, red, table]`
+    pub cards: [Card; 5],
+    pub moves: Vec<Move>,
+}
+
+impl GameRecord {
+    pub fn new(cards: [Card; 5]) -> Self {
+        Self {
+            cards,
+            moves: Vec::new(),
+        }
+    }
+
+    /// Checks that the colors take turns from the first player and replays the moves.
+    pub fn from_tagged_moves(
+        cards: [Card; 5],
+        moves: &[(Color, Move)],
+    ) -> Result<Self, RecordError> {
+        let mut record = GameRecord::new(cards);
+        let mut to_move = cards[4].color();
+        for (i, &(color, mv)) in moves.iter().enumerate() {
+            if color != to_move {
+                return Err(RecordError::Turn(i + 1, color));
+            }
+            record.moves.push(mv);
+            to_move = to_move.opponent();
+        }
+        record.replay()?;
+        Ok(record)
+    }
+
+    /// The moves with the color that played them, the first player has the color of the table card.
+    pub fn tagged_moves(&self) -> Vec<(Color, Move)> {
+        let mut color = self.cards[4].color();
+        self.moves
+            .iter()
+            .map(|&mv| {
+                let tagged = (color, mv);
+                color = color.opponent();
+                tagged
+            })
+            .collect()
+    }
+
+    pub fn start(&self) -> Result<Game, RecordError> {
+        Ok(Game::start_with(self.cards)?)
+    }
+
+    /// Plays all moves from the start, checking that each of them is legal.
+    pub fn replay(&self) -> Result<Vec<Game>, RecordError> {
+        let mut games = vec![self.start()?];
+        for (i, &mv) in self.moves.iter().enumerate() {
+            let game = games[games.len() - 1];
+            if game.is_loss() || !game.legal_moves_small().contains(&mv) {
+                return Err(RecordError::Illegal(i + 1, mv));
+            }
+            games.push(game.apply(mv));
+        }
+        Ok(games)
+    }
+
+    pub fn to_text(&self) -> String {
+        let names: Vec<&str> = self.cards.iter().map(|card| card.name()).collect();
+        let mut res = format!(
+            "cards: {}\nfirst: {}\n",
+            names.join(" "),
+            self.cards[4].color()
+        );
+        for (i, mv) in self.moves.iter().enumerate() {
+            res.push_str(&format!("{}. {}\n", i + 1, mv));
+        }
+        res
+    }
+
+    /// Parses `to_text` and replays the moves. Empty lines are skipped.
+    pub fn from_text(text: &str) -> Result<Self, RecordError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let mut header = |key: &str| {
+            let line = lines.next().unwrap_or_default();
+            match line.split_once(':') {
+                Some((k, value)) if k.trim() == key => Ok(value.trim()),
+                _ => Err(RecordError::Header(line.to_string())),
+            }
+        };
+
+        let names = header("cards")?;
+        let cards = names
+            .split_whitespace()
+            .map(Card::from_name)
+            .collect::<Result<Vec<Card>, CardError>>()?;
+        let cards = match cards.as_slice() {
+            &[a, b, c, d, e] => [a, b, c, d, e],
+            _ => return Err(CardError::Count(cards.len()).into()),
+        };
+        if header("first")? != cards[4].color().name() {
+            return Err(RecordError::FirstPlayer);
+        }
+
+        let mut record = GameRecord::new(cards);
+        for (i, line) in lines.enumerate() {
+            let number = i + 1;
+            let mv = line
+                .split_once('.')
+                .filter(|(n, _)| n.trim().parse() == Ok(number))
+                .and_then(|(_, notation)| Move::from_notation(notation))
+                .ok_or_else(|| RecordError::Notation(number, line.to_string()))?;
+            record.moves.push(mv);
+        }
+        record.replay()?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+    use crate::state::EngineState;
+
+    fn cards() -> [Card; 5] {
+        [Card(0), Card(1), Card(2), Card(3), Card(4)]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rng = Rng::with_seed(13);
+        for _ in 0..20 {
+            let mut record = GameRecord::new(cards());
+            let mut game = record.start().unwrap();
+            while !game.is_loss() && record.moves.len() < 100 {
+                let moves = game.legal_moves_small();
+                let mv = moves[rng.usize(0..moves.len())];
+                record.moves.push(mv);
+                game = game.apply(mv);
+            }
+            let text = record.to_text();
+            let parsed = GameRecord::from_text(&text).unwrap();
+            assert_eq!(parsed, record);
+            assert_eq!(parsed.replay().unwrap().last(), Some(&game));
+        }
+    }
+
+    #[test]
+    fn test_tagged_moves() {
+        let mut state = EngineState::new(cards()).unwrap();
+        let rng = Rng::with_seed(20);
+        while !state.game().is_loss() && state.ply() < 40 {
+            let moves = state.game().legal_moves_small();
+            state.play(moves[rng.usize(0..moves.len())]);
+        }
+        let tagged: Vec<(Color, Move)> = state.tagged_moves().collect();
+        let record = GameRecord::from_tagged_moves(cards(), &tagged).unwrap();
+        assert_eq!(record.moves, state.moves().collect::<Vec<_>>());
+        assert_eq!(record.tagged_moves(), tagged);
+        // crab is blue, so blue starts
+        assert_eq!(tagged[0].0, Color::Blue);
+        assert_eq!(tagged[1].0, Color::Red);
+
+        let mut swapped = tagged.clone();
+        swapped[1].0 = Color::Blue;
+        assert_eq!(
+            GameRecord::from_tagged_moves(cards(), &swapped),
+            Err(RecordError::Turn(2, Color::Blue))
+        );
+        let illegal = [(
+            Color::Blue,
+            Move {
+                from: 0,
+                to: 1,
+                card: 0,
+            },
+        )];
+        assert_eq!(
+            GameRecord::from_tagged_moves(cards(), &illegal),
+            Err(RecordError::Illegal(1, illegal[0].1))
+        );
+    }
+
+    #[test]
+    fn test_from_text() {
+        let text = "cards: ox boar horse elephant crab\nfirst: blue\n\n1. e1e2 ox\n";
+        let record = GameRecord::from_text(text).unwrap();
+        assert_eq!(record.cards, cards());
+        assert_eq!(
+            record.moves,
+            vec![Move {
+                from: 0,
+                to: 5,
+                card: 0
+            }]
+        );
+
+        let red = "cards: ox boar horse elephant crab\nfirst: red\n";
+        assert_eq!(GameRecord::from_text(red), Err(RecordError::FirstPlayer));
+        let missing = "first: blue\n";
+        assert_eq!(
+            GameRecord::from_text(missing),
+            Err(RecordError::Header("first: blue".to_string()))
+        );
+        let numbers = "cards: ox boar horse elephant crab\nfirst: blue\n2. e1e2 ox\n";
+        assert!(matches!(
+            GameRecord::from_text(numbers),
+            Err(RecordError::Notation(1, _))
+        ));
+        // the pawn on 0 can not move sideways with ox
+        let illegal = "cards: ox boar horse elephant crab\nfirst: blue\n1. e1d1 ox\n";
+        assert!(matches!(
+            GameRecord::from_text(illegal),
+            Err(RecordError::Illegal(1, _))
+        ));
+    }
+}
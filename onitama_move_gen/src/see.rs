@@ -0,0 +1,119 @@
+use crate::{
+    gen::{CaptureKind, Game, PIECE_MASK},
+    moves::Move,
+    ops::CardIter,
+    SHIFTED_R,
+};
+
+pub const PAWN_VALUE: i32 = 100;
+pub const KING_VALUE: i32 = 10000;
+
+impl Game {
+    /// Static exchange evaluation with a single exchange.
+    /// The gain is the captured piece, minus the moved piece if the opponent can take it back.
+    /// Cards rotate after every move, so the exchange is not followed further:
+    /// the recapture only depends on the two cards the opponent holds right now.
+    /// Taking the king ends the game, so it is never recaptured.
+    pub fn see(&self, mv: Move) -> i32 {
+        let gain = match self.capture_target(mv.to) {
+            None => 0,
+            Some(CaptureKind::Pawn) => PAWN_VALUE,
+            Some(CaptureKind::King) => return KING_VALUE,
+        };
+        if self.other_reaches(mv.to as u32) {
+            let moved = if mv.from as u32 == self.my.wrapping_shr(25) {
+                KING_VALUE
+            } else {
+                PAWN_VALUE
+            };
+            gain - moved
+        } else {
+            gain
+        }
+    }
+
+    /// Whether any of the other pieces can move to `to` (in the frame of the player to move).
+    #[inline]
+    fn other_reaches(&self, to: u32) -> bool {
+        let pos = 24 - to as usize;
+        let mut cards = CardIter::new(self.cards.wrapping_shr(16));
+        let reach = SHIFTED_R[cards.next().unwrap() as usize][pos]
+            | SHIFTED_R[cards.next().unwrap() as usize][pos];
+        reach & self.other & PIECE_MASK & !(1 << pos) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // my pieces: king on 2, pawns on 7 and 11
+    // other pieces: king on 22, pawns on 12 and 16 (in my frame)
+    // I hold ox and boar, the other player holds elephant and rabbit
+    const GAME: Game = Game {
+        my: 1 << 2 | 1 << 7 | 1 << 11 | 2 << 25,
+        other: 1 << 2 | 1 << 12 | 1 << 8 | 2 << 25,
+        cards: (1 << 0 | 1 << 1) | (1 << 3 | 1 << 11) << 16,
+        table: 4,
+    };
+
+    #[test]
+    fn test_see() {
+        // 7 takes 12 with ox, 16 takes back with elephant
+        let mv = Move {
+            from: 7,
+            to: 12,
+            card: 0,
+        };
+        assert_eq!(GAME.see(mv), 0);
+        // 11 takes 16 with ox, 12 takes back with rabbit
+        let mv = Move {
+            from: 11,
+            to: 16,
+            card: 0,
+        };
+        assert_eq!(GAME.see(mv), 0);
+        // quiet move onto a square that 12 reaches
+        let mv = Move {
+            from: 7,
+            to: 8,
+            card: 1,
+        };
+        assert_eq!(GAME.see(mv), -PAWN_VALUE);
+        // quiet king move that is safe
+        let mv = Move {
+            from: 2,
+            to: 1,
+            card: 1,
+        };
+        assert_eq!(GAME.see(mv), 0);
+    }
+
+    #[test]
+    fn test_see_undefended() {
+        let game = Game {
+            other: 1 << 2 | 1 << 12 | 2 << 25,
+            ..GAME
+        };
+        let mv = Move {
+            from: 7,
+            to: 12,
+            card: 0,
+        };
+        assert_eq!(game.see(mv), PAWN_VALUE);
+    }
+
+    #[test]
+    fn test_see_king() {
+        let game = Game {
+            other: 1 << 2 | 1 << 12 | 12 << 25,
+            ..GAME
+        };
+        let mv = Move {
+            from: 7,
+            to: 12,
+            card: 0,
+        };
+        assert_eq!(game.see(mv), KING_VALUE);
+    }
+}
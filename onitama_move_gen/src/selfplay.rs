@@ -0,0 +1,89 @@
+use crate::{
+    card::{Card, CardError},
+    gen::Game,
+    moves::Move,
+    ops::SplitMix64,
+    record::GameRecord,
+};
+
+/// Plays uniformly random moves for self-play datasets.
+/// The moves are picked by index into `Game::forward_ordered` with a splitmix64 generator
+/// that is part of this crate, so a seed gives the same games on every machine
+/// and with every version of the crate, which is not promised by `fastrand`.
+#[derive(Clone, Debug)]
+pub struct RandomPolicy {
+    rng: SplitMix64,
+}
+
+impl RandomPolicy {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// `None` if the game is over.
+    pub fn choose(&mut self, game: &Game) -> Option<Move> {
+        if game.is_loss() {
+            return None;
+        }
+        let children = game.forward_ordered();
+        let index = self.rng.next() % children.len() as u64;
+        Some(children[index as usize].0)
+    }
+
+    /// A game from the start with `cards` until it is over or `max_plies` moves were played.
+    pub fn play(&mut self, cards: [Card; 5], max_plies: usize) -> Result<GameRecord, CardError> {
+        let mut record = GameRecord::new(cards);
+        let mut game = Game::start_with(cards)?;
+        while record.moves.len() < max_plies {
+            let mv = match self.choose(&game) {
+                Some(mv) => mv,
+                None => break,
+            };
+            record.moves.push(mv);
+            game = game.apply(mv);
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards() -> [Card; 5] {
+        [Card(0), Card(1), Card(2), Card(3), Card(4)]
+    }
+
+    #[test]
+    fn test_random_policy() {
+        let record = RandomPolicy::new(7).play(cards(), 8).unwrap();
+        let expected: Vec<Move> = [
+            "c1c2 boar",
+            "b1c2 elephant",
+            "d1d2 crab",
+            "d1d2 boar",
+            "a1a2 ox",
+            "e1d1 horse",
+            "b1c1 elephant",
+            "d1e1 ox",
+        ]
+        .iter()
+        .map(|notation| Move::from_notation(notation).unwrap())
+        .collect();
+        // pinned, this must not change between versions
+        assert_eq!(record.moves, expected);
+        assert!(record.replay().is_ok());
+        assert_eq!(RandomPolicy::new(7).play(cards(), 8).unwrap(), record);
+        assert_ne!(RandomPolicy::new(8).play(cards(), 8).unwrap(), record);
+
+        let mut policy = RandomPolicy::new(0);
+        let record = policy.play(cards(), 1000).unwrap();
+        let end = *record.replay().unwrap().last().unwrap();
+        assert!(end.is_loss() || record.moves.len() == 1000);
+        if end.is_loss() {
+            assert_eq!(policy.choose(&end), None);
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use fastrand::Rng;
+
+use crate::{
+    board::BoardError,
+    card::{check_distinct, Card, CardError, Color},
+    gen::{Game, PIECE_MASK},
+    ops::SplitMix64,
+};
+
+pub const START_PIECES: u32 = 0b11111 | 2 << 25;
+
+/// Starting pieces of one player in their own frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    pub pawns: u32,
+    pub king: u8,
+}
+
+impl Layout {
+    pub const STANDARD: Layout = Layout {
+        pawns: 0b11011,
+        king: 2,
+    };
+
+    /// Packs the layout like `Game::my`.
+    pub fn pieces(self) -> Result<u32, BoardError> {
+        let king = self.king as u32;
+        if king >= 25 {
+            return Err(BoardError::KingSquare(king));
+        }
+        if self.pawns & !PIECE_MASK != 0 {
+            return Err(BoardError::PawnSquare(
+                (self.pawns & !PIECE_MASK).trailing_zeros(),
+            ));
+        }
+        if self.pawns & 1 << king != 0 {
+            return Err(BoardError::Overlap(king));
+        }
+        Ok(self.pawns | 1 << king | king << 25)
+    }
+}
+
+impl Game {
+    /// Cards are given as `[blue, blue, red, red, table]`.
+    /// The player whose color matches the table card moves first.
+    pub fn start_with(cards: [Card; 5]) -> Result<Self, CardError> {
+        check_distinct(&cards)?;
+        let [blue1, blue2, red1, red2, table] = cards;
+        let blue = 1 << blue1.0 | 1 << blue2.0;
+        let red = 1 << red1.0 | 1 << red2.0;
+        let (my_cards, other_cards) = match table.color() {
+            Color::Blue => (blue, red),
+            Color::Red => (red, blue),
+        };
+        Ok(Game {
+            my: START_PIECES,
+            other: START_PIECES,
+            cards: my_cards | other_cards << 16,
+            table: table.0,
+        })
+    }
+
+    /// Same as `start_with`, but with the pieces of both players given by `blue` and `red`.
+    pub fn start_with_layout(
+        cards: [Card; 5],
+        blue: Layout,
+        red: Layout,
+    ) -> Result<Self, BoardError> {
+        let mut game = Self::start_with(cards)?;
+        let (my, other) = match cards[4].color() {
+            Color::Blue => (blue, red),
+            Color::Red => (red, blue),
+        };
+        game.my = my.pieces()?;
+        game.other = other.pieces()?;
+        game.validate()?;
+        Ok(game)
+    }
+
+    /// Parses five comma separated card names, see `start_with` for the order.
+    pub fn standard_opening(names: &str) -> Result<Self, CardError> {
+        let cards = names
+            .split(',')
+            .map(Card::from_name)
+            .collect::<Result<Vec<_>, _>>()?;
+        if cards.len() != 5 {
+            return Err(CardError::Count(cards.len()));
+        }
+        Self::start_with([cards[0], cards[1], cards[2], cards[3], cards[4]])
+    }
+
+    /// The same pieces with the cards replaced, `my` and `other` are the hands of
+    /// the player to move and of the other player. The five cards have to be different
+    /// cards out of the sixteen.
+    pub fn with_cards(
+        &self,
+        my: [Card; 2],
+        other: [Card; 2],
+        table: Card,
+    ) -> Result<Self, CardError> {
+        check_distinct(&[my[0], my[1], other[0], other[1], table])?;
+        Ok(Game {
+            cards: (1 << my[0].0 | 1 << my[1].0) | (1 << other[0].0 | 1 << other[1].0) << 16,
+            table: table.0,
+            ..*self
+        })
+    }
+}
+
+/// Sets up a game one piece at a time.
+/// All squares are in the frame of the player to move, `build` turns the board for the other player.
+#[derive(Clone, Default, Debug)]
+pub struct PositionBuilder {
+    my_kings: Vec<u8>,
+    other_kings: Vec<u8>,
+    my_pawns: Vec<u8>,
+    other_pawns: Vec<u8>,
+    cards: Option<[Card; 4]>,
+    table: Option<Card>,
+}
+
+impl PositionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn my_king(mut self, pos: u8) -> Self {
+        self.my_kings.push(pos);
+        self
+    }
+
+    pub fn my_pawn(mut self, pos: u8) -> Self {
+        self.my_pawns.push(pos);
+        self
+    }
+
+    pub fn other_king(mut self, pos: u8) -> Self {
+        self.other_kings.push(pos);
+        self
+    }
+
+    pub fn other_pawn(mut self, pos: u8) -> Self {
+        self.other_pawns.push(pos);
+        self
+    }
+
+    /// My two cards followed by the two cards of the other player.
+    pub fn cards(mut self, cards: [Card; 4]) -> Self {
+        self.cards = Some(cards);
+        self
+    }
+
+    pub fn table(mut self, card: Card) -> Self {
+        self.table = Some(card);
+        self
+    }
+
+    /// Checks that each player has one king, that squares are on the board and used once,
+    /// and everything `Game::validate` checks.
+    pub fn build(&self) -> Result<Game, BoardError> {
+        let mut used = 0u32;
+        let mut place = |pos: u8, error: fn(u32) -> BoardError| {
+            let pos = pos as u32;
+            if pos >= 25 {
+                return Err(error(pos));
+            }
+            if used & 1 << pos != 0 {
+                return Err(BoardError::Overlap(pos));
+            }
+            used |= 1 << pos;
+            Ok(pos)
+        };
+
+        let mut sides = [0; 2];
+        let players = [
+            (&self.my_kings, &self.my_pawns),
+            (&self.other_kings, &self.other_pawns),
+        ];
+        for (side, (kings, pawns)) in sides.iter_mut().zip(players.iter()) {
+            if kings.len() != 1 {
+                return Err(BoardError::KingCount(kings.len()));
+            }
+            let king = place(kings[0], BoardError::KingSquare)?;
+            *side = 1 << king | king << 25;
+            for &pawn in pawns.iter() {
+                *side |= 1 << place(pawn, BoardError::PawnSquare)?;
+            }
+        }
+        let [my, other] = sides;
+        // the other player sees the board turned around
+        let other_king = 24 - other.wrapping_shr(25);
+        let other = (other & PIECE_MASK).reverse_bits() >> 7 | other_king << 25;
+
+        let (cards, table) = match (self.cards, self.table) {
+            (Some(cards), Some(table)) => (cards, table),
+            (cards, table) => {
+                let count = cards.map_or(0, |_| 4) + table.map_or(0, |_| 1);
+                return Err(CardError::Count(count).into());
+            }
+        };
+        let [my1, my2, other1, other2] = cards;
+        let game = Game {
+            my,
+            other,
+            cards: 1 << my1.0 | 1 << my2.0 | (1 << other1.0 | 1 << other2.0) << 16,
+            table: table.0,
+        };
+        game.validate()?;
+        Ok(game)
+    }
+}
+
+/// Distinct cards to deal games from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Deck(Vec<Card>);
+
+impl Deck {
+    /// All sixteen cards.
+    pub fn standard() -> Self {
+        Deck((0..16).map(Card).collect())
+    }
+
+    pub fn new(cards: Vec<Card>) -> Result<Self, CardError> {
+        check_distinct(&cards)?;
+        if cards.len() < 5 {
+            return Err(CardError::Count(cards.len()));
+        }
+        Ok(Deck(cards))
+    }
+
+    #[inline]
+    pub fn cards(&self) -> &[Card] {
+        &self.0
+    }
+
+    /// Shuffles the deck with `rng` and deals the first five cards in the order of `start_with`.
+    /// The deal only depends on the state of `rng`, so `Rng::with_seed(seed)` gives the
+    /// same game for the same seed and deck with the same version of `fastrand`.
+    /// Use `deal_seeded` for deals that are replayed by seed.
+    pub fn deal(&self, rng: &Rng) -> Game {
+        let mut cards = self.0.clone();
+        rng.shuffle(&mut cards);
+        Self::start(&cards)
+    }
+
+    /// Like `deal`, but shuffled with a generator that is part of this crate,
+    /// so a seed gives the same game on every machine and with every version.
+    pub fn deal_seeded(&self, seed: u64) -> Game {
+        let mut rng = SplitMix64(seed);
+        let mut cards = self.0.clone();
+        for i in (1..cards.len()).rev() {
+            let j = rng.next() % (i as u64 + 1);
+            cards.swap(i, j as usize);
+        }
+        Self::start(&cards)
+    }
+
+    fn start(cards: &[Card]) -> Game {
+        Game::start_with([cards[0], cards[1], cards[2], cards[3], cards[4]]).unwrap()
+    }
+
+    pub fn standard_deal(rng: &Rng) -> Game {
+        Self::standard().deal(rng)
+    }
+}
+
+/// Deals five distinct cards out of the sixteen.
+/// As in `start_with`, the player whose color matches the table card moves first.
+pub fn deal(rng: &Rng) -> Game {
+    Deck::standard_deal(rng)
+}
+
+/// Positions from random playouts of random deals, none of them finished.
+#[cfg(test)]
+pub(crate) fn random_games(rng: &Rng, count: usize) -> Vec<Game> {
+    let mut games = Vec::with_capacity(count);
+    while games.len() < count {
+        let mut game = deal(rng);
+        while games.len() < count && !game.is_loss() {
+            games.push(game);
+            let children: Vec<Game> = game.forward().collect();
+            game = children[rng.usize(0..children.len())];
+        }
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_position_builder() {
+        let cards = [Card(0), Card(1), Card(2), Card(3)];
+        let builder = PositionBuilder::new()
+            .my_king(2)
+            .other_king(22)
+            .cards(cards)
+            .table(Card(4));
+        let start = (0..5)
+            .filter(|&pos| pos != 2)
+            .fold(builder.clone(), |builder, pos| {
+                builder.my_pawn(pos).other_pawn(24 - pos)
+            });
+        assert_eq!(start.build(), Ok(TEST_GAME));
+
+        // the other king on 17 in my frame is on 7 in its own frame
+        let game = builder
+            .clone()
+            .other_king(17)
+            .my_pawn(12)
+            .other_pawn(13)
+            .build();
+        assert_eq!(game, Err(BoardError::KingCount(2)));
+        let game = PositionBuilder::new()
+            .my_king(0)
+            .my_pawn(12)
+            .other_king(17)
+            .other_pawn(13)
+            .cards(cards)
+            .table(Card(4))
+            .build()
+            .unwrap();
+        assert_eq!(game.my, 1 << 0 | 1 << 12);
+        assert_eq!(game.other, 1 << 7 | 1 << 11 | 7 << 25);
+
+        let cases = [
+            (
+                PositionBuilder::new().other_king(22),
+                BoardError::KingCount(0),
+            ),
+            (builder.clone().my_pawn(25), BoardError::PawnSquare(25)),
+            (builder.clone().my_pawn(22), BoardError::Overlap(22)),
+            (builder.clone().other_pawn(2), BoardError::Overlap(2)),
+            (
+                PositionBuilder::new().my_king(2).other_king(30),
+                BoardError::KingSquare(30),
+            ),
+            (
+                builder.clone().table(Card(3)),
+                BoardError::Cards(CardError::Duplicate(Card(3))),
+            ),
+            (
+                PositionBuilder::new().my_king(2).other_king(22),
+                BoardError::Cards(CardError::Count(0)),
+            ),
+            // my king is already on the temple of the other player
+            (
+                PositionBuilder::new()
+                    .my_king(22)
+                    .other_king(12)
+                    .cards(cards)
+                    .table(Card(4)),
+                BoardError::AlreadyWon,
+            ),
+        ];
+        for (builder, err) in cases.iter() {
+            assert_eq!(builder.build().as_ref(), Err(err));
+        }
+    }
+
+    #[test]
+    fn test_standard_opening() {
+        let game = Game::standard_opening("tiger,crab,horse,mantis,frog").unwrap();
+        // frog is red, so red moves first
+        assert_eq!(game.my, START_PIECES);
+        assert_eq!(game.other, START_PIECES);
+        assert_eq!(game.cards, (1 << 2 | 1 << 9) | (1 << 5 | 1 << 4) << 16);
+        assert_eq!(game.table, 10);
+
+        let game = Game::standard_opening("ox, boar, horse, elephant, crab").unwrap();
+        assert_eq!(game, TEST_GAME);
+    }
+
+    #[test]
+    fn test_standard_opening_errors() {
+        assert_eq!(
+            Game::standard_opening("tiger,crab,horse,lion,frog"),
+            Err(CardError::Unknown("lion".to_string()))
+        );
+        assert_eq!(
+            Game::standard_opening("tiger,crab,horse,crab,frog"),
+            Err(CardError::Duplicate(Card(4)))
+        );
+        assert_eq!(
+            Game::standard_opening("tiger,crab,horse,frog"),
+            Err(CardError::Count(4))
+        );
+    }
+
+    #[test]
+    fn test_with_cards() {
+        let [ox, boar, horse, elephant, crab] = [Card(0), Card(1), Card(2), Card(3), Card(4)];
+        assert_eq!(
+            TEST_GAME.with_cards([ox, boar], [horse, elephant], crab),
+            Ok(TEST_GAME)
+        );
+        assert_eq!(
+            TEST_GAME.with_cards([ox, boar], [horse, ox], crab),
+            Err(CardError::Duplicate(ox))
+        );
+        // an index past the sixteen cards would be shifted into the other hand
+        assert_eq!(
+            TEST_GAME.with_cards([ox, Card(20)], [horse, elephant], crab),
+            Err(CardError::Index(20))
+        );
+        assert_eq!(
+            TEST_GAME.with_cards([ox, boar], [horse, elephant], Card(40)),
+            Err(CardError::Index(40))
+        );
+
+        // ox takes the other king in front of my king, elephant and tiger do not
+        let tiger = Card(5);
+        let game = Game {
+            my: 1 << 11 | 11 << 25,
+            other: 1 << 8 | 8 << 25,
+            ..TEST_GAME
+        };
+        let win = game
+            .with_cards([ox, tiger], [horse, crab], elephant)
+            .unwrap();
+        assert!(win.is_win());
+        let no_win = game
+            .with_cards([elephant, tiger], [horse, crab], ox)
+            .unwrap();
+        assert!(!no_win.is_win());
+        assert!(no_win.forward().all(|new_game| !new_game.is_loss()));
+    }
+
+    #[test]
+    fn test_deal() {
+        for seed in 0..1000 {
+            let game = deal(&Rng::with_seed(seed));
+            assert_eq!(game.my, START_PIECES);
+            assert_eq!(game.other, START_PIECES);
+            assert_eq!(game.cards.count_ones(), 4);
+            assert_eq!(game.cards & 0xffff & game.cards >> 16, 0);
+            assert_eq!((game.cards | game.cards >> 16) & 1 << game.table, 0);
+            assert!(game.table < 16);
+            assert_eq!(game.count_moves(), game.forward().count() as u64);
+        }
+        assert_eq!(deal(&Rng::with_seed(7)), deal(&Rng::with_seed(7)));
+    }
+
+    #[test]
+    fn test_deck() {
+        assert_eq!(Deck::standard().cards().len(), 16);
+        for seed in 0..100 {
+            let game = Deck::standard_deal(&Rng::with_seed(seed));
+            assert_eq!(game, Deck::standard_deal(&Rng::with_seed(seed)));
+            assert_eq!(game, deal(&Rng::with_seed(seed)));
+        }
+
+        // a seeded deal never changes
+        let game = Deck::standard().deal_seeded(7);
+        // monkey and eel against horse and crab, tiger on the table
+        assert_eq!(
+            game,
+            Game {
+                my: START_PIECES,
+                other: START_PIECES,
+                cards: (1 << 6 | 1 << 14) | (1 << 2 | 1 << 4) << 16,
+                table: 5,
+            }
+        );
+        assert_ne!(game, Deck::standard().deal_seeded(8));
+
+        let cards: Vec<Card> = [0, 1, 2, 3, 4].iter().map(|&i| Card(i)).collect();
+        let deck = Deck::new(cards.clone()).unwrap();
+        let game = deck.deal(&Rng::with_seed(0));
+        let mut dealt: Vec<Card> = (0..16)
+            .filter(|i| game.cards & (1 << i | 1 << (i + 16)) != 0)
+            .map(Card)
+            .collect();
+        dealt.push(Card(game.table));
+        dealt.sort();
+        assert_eq!(dealt, cards);
+
+        assert_eq!(Deck::new(cards[..4].to_vec()), Err(CardError::Count(4)));
+        assert_eq!(
+            Deck::new(vec![Card(0), Card(1), Card(2), Card(3), Card(1)]),
+            Err(CardError::Duplicate(Card(1)))
+        );
+    }
+
+    #[test]
+    fn test_start_with_layout() {
+        let cards = [Card(0), Card(1), Card(2), Card(3), Card(4)];
+        let standard = Game::start_with_layout(cards, Layout::STANDARD, Layout::STANDARD);
+        assert_eq!(standard, Ok(TEST_GAME));
+
+        // crab is blue, so blue moves first with ox and boar
+        let corner = Layout {
+            pawns: 1 << 1,
+            king: 0,
+        };
+        let game = Game::start_with_layout(cards, corner, Layout::STANDARD).unwrap();
+        assert_eq!(game.my, 0b11);
+        assert_eq!(game.other, START_PIECES);
+        let moves: Vec<(u8, u8, u8)> = game
+            .legal_moves_vec()
+            .iter()
+            .map(|mv| (mv.from, mv.to, mv.card))
+            .collect();
+        let expected = [(0, 5, 0), (0, 5, 1), (1, 6, 0), (1, 2, 1), (1, 6, 1)];
+        assert_eq!(moves, expected);
+        assert_eq!(game.count_moves(), 5);
+
+        let bad_king = Layout {
+            pawns: 0b11,
+            king: 1,
+        };
+        assert_eq!(
+            Game::start_with_layout(cards, bad_king, Layout::STANDARD),
+            Err(BoardError::Overlap(1))
+        );
+        // the red pieces end up on the same squares as the blue ones
+        let far = Layout {
+            pawns: 1 << 20,
+            king: 24,
+        };
+        assert_eq!(
+            Game::start_with_layout(cards, Layout::STANDARD, far),
+            Err(BoardError::Overlap(0))
+        );
+        let off_board = Layout {
+            pawns: 1 << 25,
+            king: 2,
+        };
+        assert_eq!(
+            Game::start_with_layout(cards, off_board, Layout::STANDARD),
+            Err(BoardError::PawnSquare(25))
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    attack::Attacks,
+    card::{Card, CardError, Color},
+    gen::Game,
+    moves::{Move, MoveEffect},
+};
+
+/// What is needed to take back one move, see `Game::undo_move`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Undo {
+    /// The player who made the move, who is to move again after it is taken back.
+    pub color: Color,
+    pub mv: Move,
+    pub effect: MoveEffect,
+    /// The attacks before the move, which `EngineState::undo` restores.
+    pub attacks: Attacks,
+}
+
+/// A game in progress for interactive play, which knows the color of the player to move
+/// and the number of plies played, unlike `Game`. Every move can be taken back.
+#[derive(Clone, Debug)]
+pub struct EngineState {
+    game: Game,
+    ply: u32,
+    to_move: Color,
+    attacks: Attacks,
+    history: Vec<Undo>,
+}
+
+impl Game {
+    /// The color that won, for a game started by `start_side` after `ply` plies.
+    /// `None` while the game is not over.
+    pub fn winner(&self, start_side: Color, ply: u32) -> Option<Color> {
+        let to_move = if ply & 1 == 0 {
+            start_side
+        } else {
+            start_side.opponent()
+        };
+        if self.is_loss() {
+            Some(to_move.opponent())
+        } else if self.is_other_loss() {
+            Some(to_move)
+        } else {
+            None
+        }
+    }
+}
+
+impl EngineState {
+    /// Starts like `Game::start_with`, so the color of the table card moves first.
+    pub fn new(cards: [Card; 5]) -> Result<Self, CardError> {
+        let game = Game::start_with(cards)?;
+        Ok(Self {
+            game,
+            ply: 0,
+            to_move: cards[4].color(),
+            attacks: game.attacks(),
+            history: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    #[inline]
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    #[inline]
+    pub fn to_move(&self) -> Color {
+        self.to_move
+    }
+
+    /// `Game::attacks` of the current game, kept up to date move by move.
+    #[inline]
+    pub fn attacks(&self) -> Attacks {
+        self.attacks
+    }
+
+    /// Moves played so far, the first one first.
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.history.iter().map(|undo| undo.mv)
+    }
+
+    /// Moves played so far with the color that played them, like `GameRecord::tagged_moves`.
+    pub fn tagged_moves(&self) -> impl Iterator<Item = (Color, Move)> + '_ {
+        self.history.iter().map(|undo| (undo.color, undo.mv))
+    }
+
+    /// Plays `mv` if it is legal and the game is not over.
+    pub fn play(&mut self, mv: Move) -> bool {
+        if self.game.is_loss() || !self.game.legal_moves_small().contains(&mv) {
+            return false;
+        }
+        let (game, effect) = self.game.apply_move_detailed(mv);
+        self.history.push(Undo {
+            color: self.to_move,
+            mv,
+            effect,
+            attacks: self.attacks,
+        });
+        self.attacks = self.attacks.after(&self.game, mv, effect);
+        self.game = game;
+        self.ply += 1;
+        self.to_move = self.to_move.opponent();
+        true
+    }
+
+    /// See `Game::winner`.
+    pub fn winner(&self) -> Option<Color> {
+        self.game.winner(self.to_move, 0)
+    }
+
+    /// Takes back the last move, `false` at the start.
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(Undo {
+                color,
+                mv,
+                effect,
+                attacks,
+            }) => {
+                self.game = self.game.undo_move(mv, effect);
+                self.attacks = attacks;
+                self.ply -= 1;
+                self.to_move = color;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_undo() {
+        let cards = [Card(5), Card(1), Card(7), Card(3), Card(8)];
+        let mut state = EngineState::new(cards).unwrap();
+        let start = *state.game();
+        assert_eq!(state.to_move(), Color::Red);
+        assert!(!state.undo());
+
+        let rng = Rng::with_seed(16);
+        let mut games = vec![start];
+        while !state.game().is_loss() && state.ply() < 60 {
+            let moves = state.game().legal_moves_small();
+            assert!(state.play(moves[rng.usize(0..moves.len())]));
+            games.push(*state.game());
+        }
+        assert_eq!(state.moves().count() as u32, state.ply());
+        for (i, (color, mv)) in state.tagged_moves().enumerate() {
+            assert_eq!(color == Color::Red, i & 1 == 0);
+            assert_eq!(Some(mv), state.moves().nth(i));
+        }
+        let ply = state.ply();
+        let to_move = state.to_move();
+
+        while state.undo() {
+            assert_eq!(*state.game(), games[state.ply() as usize]);
+        }
+        assert_eq!(*state.game(), start);
+        assert_eq!(state.ply(), 0);
+        assert_eq!(state.to_move(), Color::Red);
+        assert_eq!(to_move == Color::Red, ply & 1 == 0);
+    }
+
+    #[test]
+    fn test_undo_attacks() {
+        let cards = [Card(0), Card(9), Card(4), Card(15), Card(11)];
+        let rng = Rng::with_seed(23);
+        let mut captures = 0;
+        for _ in 0..50 {
+            let mut state = EngineState::new(cards).unwrap();
+            for _ in 0..100 {
+                if state.game().is_loss() || rng.usize(0..4) == 0 {
+                    state.undo();
+                } else {
+                    let moves = state.game().legal_moves_small();
+                    let mv = moves[rng.usize(0..moves.len())];
+                    captures += state.game().capture_target(mv.to).is_some() as usize;
+                    assert!(state.play(mv));
+                }
+                assert_eq!(state.attacks(), state.game().attacks());
+            }
+            while state.undo() {
+                assert_eq!(state.attacks(), state.game().attacks());
+            }
+        }
+        assert!(captures > 0);
+    }
+
+    #[test]
+    fn test_winner() {
+        let cards = [Card(5), Card(1), Card(7), Card(3), Card(8)];
+        let rng = Rng::with_seed(17);
+        let mut wins = [0; 2];
+        for _ in 0..20 {
+            let mut state = EngineState::new(cards).unwrap();
+            let mut last = None;
+            while !state.game().is_loss() && state.ply() < 200 {
+                assert_eq!(state.winner(), None);
+                let moves = state.game().legal_moves_small();
+                last = Some(state.to_move());
+                state.play(moves[rng.usize(0..moves.len())]);
+            }
+            if !state.game().is_loss() {
+                continue;
+            }
+            // the player who made the last move won
+            let winner = state.game().winner(Color::Red, state.ply());
+            assert_eq!(winner, last);
+            assert_eq!(state.winner(), last);
+            wins[(winner == Some(Color::Blue)) as usize] += 1;
+        }
+        assert!(wins[0] > 0 && wins[1] > 0);
+    }
+}
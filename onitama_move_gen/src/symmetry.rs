@@ -0,0 +1,206 @@
+use crate::{
+    gen::{Game, PIECE_MASK},
+    moves::Move,
+    ops::BitIter,
+    MIRRORED, ROTATED,
+};
+
+#[inline]
+pub fn mirror_square(pos: u32) -> u32 {
+    pos - pos % 5 + 4 - pos % 5
+}
+
+#[inline]
+fn mirror_pieces(pieces: u32) -> u32 {
+    let mut res = mirror_square(pieces.wrapping_shr(25)) << 25;
+    for pos in BitIter(pieces & PIECE_MASK) {
+        res |= 1 << mirror_square(pos);
+    }
+    res
+}
+
+#[inline]
+fn mirror_cards(cards: u32) -> u32 {
+    let mut res = 0;
+    for card in BitIter(cards) {
+        res |= 1 << (card & !15) << MIRRORED[card as usize & 15];
+    }
+    res
+}
+
+#[inline]
+fn rotate_pieces(pieces: u32) -> u32 {
+    let king = 24 - pieces.wrapping_shr(25);
+    (pieces & PIECE_MASK).reverse_bits() >> 7 | king << 25
+}
+
+fn rotate_cards(cards: u32) -> Option<u32> {
+    let mut res = 0;
+    for card in BitIter(cards) {
+        res |= 1 << (card & !15) << ROTATED[card as usize & 15]?;
+    }
+    Some(res)
+}
+
+impl Game {
+    /// Mirrors the board left to right, cards are replaced by their mirror image.
+    /// Both frames are mirrored the same way, so the player to move stays the same.
+    pub fn mirror(&self) -> Game {
+        Game {
+            my: mirror_pieces(self.my),
+            other: mirror_pieces(self.other),
+            cards: mirror_cards(self.cards),
+            table: MIRRORED[self.table as usize],
+        }
+    }
+
+    pub fn is_mirror_of(&self, other: &Game) -> bool {
+        *self == other.mirror()
+    }
+
+    /// Rotates the board by 180 degrees in both frames, the player to move stays the same.
+    /// Cards are replaced by their rotated image, so the moves rotate with the board,
+    /// but the temples stay where they are. This is unlike a pass, which swaps `my` and `other`.
+    /// Only some cards have a rotated image among the sixteen, otherwise this returns `None`.
+    pub fn rotate180(&self) -> Option<Game> {
+        Some(Game {
+            my: rotate_pieces(self.my),
+            other: rotate_pieces(self.other),
+            cards: rotate_cards(self.cards)?,
+            table: ROTATED[self.table as usize]?,
+        })
+    }
+}
+
+impl Move {
+    /// The same move in the mirrored game, see `Game::mirror`.
+    #[inline]
+    pub fn mirror(self) -> Move {
+        Move {
+            from: mirror_square(self.from as u32) as u8,
+            to: mirror_square(self.to as u32) as u8,
+            card: MIRRORED[self.card as usize] as u8,
+        }
+    }
+}
+
+/// A training sample together with its mirror image, which is just as good a sample.
+pub fn augment(game: &Game, mv: Move) -> [(Game, Move); 2] {
+    [(*game, mv), (game.mirror(), mv.mirror())]
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+    use crate::{perft::TEST_GAME, setup::random_games};
+
+    #[test]
+    fn test_mirror_cards() {
+        for card in 0..16 {
+            assert_eq!(MIRRORED[MIRRORED[card] as usize], card as u32);
+        }
+        // frog and rabbit
+        assert_eq!(MIRRORED[10], 11);
+        // tiger
+        assert_eq!(MIRRORED[5], 5);
+    }
+
+    #[test]
+    fn test_mirror() {
+        // king on 1, pawn on 8; other king on 21, pawn on 13 (in my frame)
+        // I hold frog and ox, the other player holds tiger and goose
+        let game = Game {
+            my: 1 << 1 | 1 << 8 | 1 << 25,
+            other: 1 << 3 | 1 << 11 | 3 << 25,
+            cards: (1 << 10 | 1 << 0) | (1 << 5 | 1 << 12) << 16,
+            table: 14,
+        };
+        // king on 3, pawn on 6; other king on 23, pawn on 11 (in my frame)
+        // I hold rabbit and horse, the other player holds tiger and rooster
+        let mirrored = Game {
+            my: 1 << 3 | 1 << 6 | 3 << 25,
+            other: 1 << 1 | 1 << 13 | 1 << 25,
+            cards: (1 << 11 | 1 << 2) | (1 << 5 | 1 << 13) << 16,
+            table: 15,
+        };
+        assert!(mirrored.is_mirror_of(&game));
+        assert!(game.is_mirror_of(&mirrored));
+        assert!(!game.is_mirror_of(&game));
+        // the start position only mirrors onto itself with symmetric cards
+        let symmetric = Game {
+            cards: 1 << 3 | 1 << 4 | (1 << 5 | 1 << 6) << 16,
+            table: 7,
+            ..TEST_GAME
+        };
+        assert!(symmetric.is_mirror_of(&symmetric));
+        assert!(!TEST_GAME.is_mirror_of(&TEST_GAME));
+    }
+
+    #[test]
+    fn test_mirror_moves() {
+        for new_game in TEST_GAME.forward() {
+            let mirrored = new_game.mirror();
+            assert_eq!(mirrored.mirror(), new_game);
+            assert_eq!(mirrored.count_moves(), new_game.count_moves());
+            assert_eq!(mirrored.is_win(), new_game.is_win());
+            let mut children: Vec<_> = new_game.forward().map(|g| g.mirror()).collect();
+            let mut mirrored_children: Vec<_> = mirrored.forward().collect();
+            children.sort_by_key(|g| (g.my, g.other, g.cards, g.table));
+            mirrored_children.sort_by_key(|g| (g.my, g.other, g.cards, g.table));
+            assert_eq!(children, mirrored_children);
+        }
+    }
+
+    #[test]
+    fn test_augment() {
+        let rng = Rng::with_seed(21);
+        for game in random_games(&rng, 1000) {
+            let moves = game.legal_moves_small();
+            let mv = moves[rng.usize(0..moves.len())];
+            let [sample, (mirrored, mirrored_mv)] = augment(&game, mv);
+            assert_eq!(sample, (game, mv));
+            assert!(mirrored.legal_moves_small().contains(&mirrored_mv));
+            assert_eq!(mirrored.apply(mirrored_mv), game.apply(mv).mirror());
+            assert_eq!((mirrored.mirror(), mirrored_mv.mirror()), (game, mv));
+        }
+    }
+
+    #[test]
+    fn test_rotate_cards() {
+        // ox and horse
+        assert_eq!(ROTATED[0], Some(2));
+        assert_eq!(ROTATED[2], Some(0));
+        // monkey
+        assert_eq!(ROTATED[6], Some(6));
+        // tiger
+        assert_eq!(ROTATED[5], None);
+        for card in 0..16 {
+            if let Some(rotated) = ROTATED[card] {
+                assert_eq!(ROTATED[rotated as usize], Some(card as u32));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotate180() {
+        assert_eq!(TEST_GAME.rotate180(), None);
+
+        let cards: Vec<u32> = (0..16).filter(|&c| ROTATED[c as usize].is_some()).collect();
+        assert!(cards.len() >= 5);
+        let game = Game {
+            cards: 1 << cards[0] | 1 << cards[1] | (1 << cards[2] | 1 << cards[3]) << 16,
+            table: cards[4],
+            ..TEST_GAME
+        };
+        let rotated = game.rotate180().unwrap();
+        assert_eq!(rotated.my, 0b11111 << 20 | 22 << 25);
+        assert_eq!(rotated.rotate180(), Some(game));
+        for new_game in game.forward() {
+            let rotated = new_game.rotate180().unwrap();
+            assert_eq!(rotated.rotate180(), Some(new_game));
+            assert_eq!(rotated.count_moves(), new_game.count_moves());
+        }
+    }
+}
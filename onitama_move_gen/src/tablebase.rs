@@ -6,10 +6,12 @@ use std::{
 };
 
 use bitintr::{Andn, Pext, Popcnt};
+use fastrand::Rng;
 
 use crate::{
     eval::Eval,
     gen::{Game, PIECE_MASK},
+    moves::Move,
     ops::{BitIter, CardIter},
 };
 
@@ -120,6 +122,26 @@ impl TableBase {
         }
     }
 
+    /// Whether `game` is solved by the table, which is when both players have at most one pawn.
+    #[inline]
+    pub fn covers(game: &Game) -> bool {
+        game.my_pawns().popcnt() <= 1 && game.other_pawns().popcnt() <= 1
+    }
+
+    /// The move that wins in the fewest plies, or else draws or loses in the most.
+    /// It leads to the position that is worst for the opponent, so along a won line
+    /// the distance to mate goes down by one each ply.
+    /// `game` has to be covered by the table, see `covers`.
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        if game.is_loss() {
+            return None;
+        }
+        game.legal_moves_small()
+            .iter()
+            .copied()
+            .min_by_key(|&mv| self[game.apply(mv)])
+    }
+
     #[inline]
     pub fn eval(&self, game: Game) -> (bool, i8) {
         let my_king = game.my.wrapping_shr(25);
@@ -220,6 +242,207 @@ impl IndexMut<Game> for TableBase {
     }
 }
 
+/// Outcome of a position with perfect play, for the player to move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wdl {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl From<Eval> for Wdl {
+    #[inline]
+    fn from(eval: Eval) -> Self {
+        match eval.cmp(&Eval::new_tie()) {
+            std::cmp::Ordering::Less => Wdl::Loss,
+            std::cmp::Ordering::Equal => Wdl::Draw,
+            std::cmp::Ordering::Greater => Wdl::Win,
+        }
+    }
+}
+
+const WDL_ENTRIES: usize = 30 * 25 * 25 * 26 * 26;
+
+/// Only the outcomes of the positions with at most `max_pieces` pieces, kings included,
+/// in 2 bits per position instead of the byte of a `TableBase`.
+/// Without the distance to mate a player that always picks some winning move can go round
+/// in circles, so this is for knowing the outcome and not for playing it out.
+pub struct WdlTableBase {
+    data: Vec<u8>,
+    max_pieces: u32,
+}
+
+impl WdlTableBase {
+    /// Keeps the outcomes of a full table, which covers every position with up to 4 pieces.
+    pub fn from_table(table: &TableBase) -> Self {
+        let mut data = vec![0; WDL_ENTRIES / 4];
+        let evals = table.0.iter().flatten().flatten().flatten().flatten();
+        for (i, &eval) in evals.enumerate() {
+            data[i / 4] |= wdl_code(Wdl::from(eval)) << (i % 4 * 2);
+        }
+        WdlTableBase {
+            data,
+            max_pieces: 4,
+        }
+    }
+
+    /// The packed outcomes, four positions to a byte in the order of `TableBase`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Inverse of `as_bytes` for a table built with `max_pieces`, `None` if the length does not match.
+    pub fn from_bytes(bytes: Vec<u8>, max_pieces: u32) -> Option<Self> {
+        if bytes.len() == WDL_ENTRIES / 4 {
+            Some(WdlTableBase {
+                data: bytes,
+                max_pieces,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn max_pieces(&self) -> u32 {
+        self.max_pieces
+    }
+
+    /// Whether `game` is solved by the table, like `TableBase::covers` but also
+    /// with at most `max_pieces` pieces on the board.
+    #[inline]
+    pub fn covers(&self, game: &Game) -> bool {
+        TableBase::covers(game) && count_pieces(game) <= self.max_pieces
+    }
+
+    /// `game` has to be covered by the table, see `covers`.
+    #[inline]
+    pub fn probe_wdl(&self, game: &Game) -> Wdl {
+        let i = wdl_index(game);
+        match self.data[i / 4] >> (i % 4 * 2) & 3 {
+            0 => Wdl::Draw,
+            1 => Wdl::Win,
+            _ => Wdl::Loss,
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, game: &Game, wdl: Wdl) {
+        let i = wdl_index(game);
+        self.data[i / 4] = self.data[i / 4] & !(3 << (i % 4 * 2)) | wdl_code(wdl) << (i % 4 * 2);
+    }
+}
+
+#[inline]
+fn wdl_code(wdl: Wdl) -> u8 {
+    match wdl {
+        Wdl::Draw => 0,
+        Wdl::Win => 1,
+        Wdl::Loss => 2,
+    }
+}
+
+#[inline]
+fn wdl_index(game: &Game) -> usize {
+    let cards = compress_cards(game.cards, game.table) as usize;
+    let my_king = game.my.wrapping_shr(25) as usize;
+    let other_king = game.other.wrapping_shr(25) as usize;
+    let my_pieces = compress_pieces(game.my) as usize;
+    let other_pieces = compress_pieces(game.other) as usize;
+    (((cards * 25 + my_king) * 25 + other_king) * 26 + my_pieces) * 26 + other_pieces
+}
+
+#[inline]
+fn count_pieces(game: &Game) -> u32 {
+    (game.my & PIECE_MASK).popcnt() + (game.other & PIECE_MASK).popcnt()
+}
+
+/// Calls `f` with every position before `game` that has at most `max_pieces` pieces,
+/// also the ones where the last move took a pawn. Positions that were already over are skipped.
+fn for_each_prev(game: &Game, max_pieces: u32, mut f: impl FnMut(Game)) {
+    for (mut prev_game, take) in game.backward() {
+        if !prev_game.is_loss() && !prev_game.is_other_loss() {
+            f(prev_game);
+        }
+        if (prev_game.other & PIECE_MASK).popcnt() < 2 {
+            prev_game.other |= take;
+            if count_pieces(&prev_game) <= max_pieces
+                && !prev_game.is_loss()
+                && !prev_game.is_other_loss()
+            {
+                f(prev_game);
+            }
+        }
+    }
+}
+
+/// Solves the positions with at most `max_pieces` pieces (2 to 4) by retrograde analysis
+/// like `TableBase::new`, but it only ever keeps the 2 bit outcomes, so it needs about
+/// a quarter of the memory. Captures only remove pieces, so these positions never lead
+/// to positions with more. Everything that is not a win or a loss at the end is a draw.
+///
+/// This is enough to know the outcome but not to play it out, see `WdlTableBase`.
+pub fn build_wdl(cards: [u32; 5], max_pieces: u32) -> WdlTableBase {
+    let mut wdl = WdlTableBase {
+        data: vec![0; WDL_ENTRIES / 4],
+        max_pieces,
+    };
+    let mut wins = Vec::new();
+    let cards = card_config(cards);
+
+    for other_king in 0..25 {
+        for (my, other) in piece_config(1 << 24 >> other_king) {
+            let full_other = 1 << 24 >> other | 1 << 24 >> other_king;
+            let my_king_iter = if other_king == 22 {
+                BitIter((1 << 22).andn(PIECE_MASK))
+            } else {
+                BitIter((1 << 22).andn(full_other))
+            };
+            for my_king in my_king_iter {
+                for &(cards, center) in &cards {
+                    let game = Game {
+                        cards,
+                        table: center,
+                        my: 1 << my & PIECE_MASK | 1 << my_king & !full_other | my_king << 25,
+                        other: 1 << other & PIECE_MASK | 1 << other_king | other_king << 25,
+                    };
+                    if count_pieces(&game) > max_pieces {
+                        continue;
+                    }
+                    wdl.set(&game, Wdl::Loss);
+                    for_each_prev(&game, max_pieces, |prev_game| {
+                        if wdl.probe_wdl(&prev_game) == Wdl::Draw {
+                            wdl.set(&prev_game, Wdl::Win);
+                            wins.push(prev_game);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    // a position is lost once every move leads to a win for the other player
+    while let Some(game) = wins.pop() {
+        for_each_prev(&game, max_pieces, |prev_game| {
+            if wdl.probe_wdl(&prev_game) != Wdl::Draw
+                || !prev_game
+                    .forward()
+                    .all(|new_game| wdl.probe_wdl(&new_game) == Wdl::Win)
+            {
+                return;
+            }
+            wdl.set(&prev_game, Wdl::Loss);
+            for_each_prev(&prev_game, max_pieces, |win| {
+                if wdl.probe_wdl(&win) == Wdl::Draw {
+                    wdl.set(&win, Wdl::Win);
+                    wins.push(win);
+                }
+            });
+        });
+    }
+
+    wdl
+}
+
 #[inline]
 fn compress_cards(cards: u32, table: u32) -> u32 {
     let combined = cards | cards.wrapping_shr(16);
@@ -268,11 +491,116 @@ fn compress_pieces(my: u32) -> u32 {
     piece_iter.next().unwrap_or(25)
 }
 
+/// Deepest distance to mate in plies that `verify` checks with forward search.
+pub const VERIFY_PLIES: u8 = 5;
+
+/// Whether the player to move can win in at most `plies` plies, found by forward search.
+pub fn search_mate(game: &Game, plies: u8) -> bool {
+    plies > 0
+        && !game.is_loss()
+        && game
+            .forward()
+            .any(|new_game| search_loss(&new_game, plies - 1))
+}
+
+/// Whether the player to move loses in at most `plies` plies whatever they play.
+fn search_loss(game: &Game, plies: u8) -> bool {
+    game.is_loss()
+        || plies > 0
+            && game
+                .forward()
+                .all(|new_game| search_mate(&new_game, plies - 1))
+}
+
+/// Plies to the end of the game by forward search, counted like `Eval::plies`.
+/// Only distances with the parity of `plies` are tried, `None` if it is more than `plies`.
+fn search_plies(game: &Game, plies: u8) -> Option<u8> {
+    (plies % 2..=plies).step_by(2).find(|&n| {
+        if n % 2 == 1 {
+            search_mate(game, n)
+        } else {
+            search_loss(game, n)
+        }
+    })
+}
+
+/// A position where the table and forward search disagree.
+#[derive(Clone, Copy, Debug)]
+pub struct Discrepancy {
+    pub game: Game,
+    pub stored: Eval,
+    /// Plies found by forward search, `None` if there is no end within the stored plies.
+    pub found: Option<u8>,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct VerifyReport {
+    pub checked: usize,
+    /// Ties and positions deeper than `VERIFY_PLIES`.
+    pub skipped: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl TableBase {
+    fn verify_game(&self, game: Game) -> Option<Discrepancy> {
+        let stored = self[game];
+        let found = search_plies(&game, stored.plies());
+        if found == Some(stored.plies()) {
+            None
+        } else {
+            Some(Discrepancy {
+                game,
+                stored,
+                found,
+            })
+        }
+    }
+
+    /// Checks the distance to mate of `sample` random positions with forward search.
+    /// `cards` are the cards the table was built with.
+    /// A win has to be found in the stored plies and not faster, a loss can not last longer.
+    pub fn verify(&self, cards: [u32; 5], sample: usize, rng: &Rng) -> VerifyReport {
+        let cards = card_config(cards);
+        let pieces = |king: u32, pawn: u32| 1 << king | (1 << pawn) & PIECE_MASK | king << 25;
+        let mut report = VerifyReport::default();
+        while report.checked + report.skipped < sample {
+            let (cards, table) = cards[rng.usize(0..cards.len())];
+            let game = Game {
+                my: pieces(rng.u32(0..25), rng.u32(0..26)),
+                other: pieces(rng.u32(0..25), rng.u32(0..26)),
+                cards,
+                table,
+            };
+            if game.validate().is_err() {
+                continue;
+            }
+            let plies = self[game].plies();
+            if plies > VERIFY_PLIES {
+                report.skipped += 1;
+                continue;
+            }
+            report.checked += 1;
+            report.discrepancies.extend(self.verify_game(game));
+        }
+        report
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
-    use super::{card_config, compress_cards, compress_pieces, piece_config, TableBase};
+    use fastrand::Rng;
+
+    use super::{
+        build_wdl, card_config, compress_cards, compress_pieces, count_pieces, piece_config,
+        search_mate, TableBase, Wdl, WdlTableBase, WDL_ENTRIES,
+    };
+    use crate::{
+        eval::Eval,
+        gen::{Game, PIECE_MASK},
+        perft::TEST_GAME,
+    };
 
     #[test]
     fn test_pieces() {
@@ -328,4 +656,118 @@ mod tests {
             println!("{}: {}", i, c);
         }
     }
+
+    #[test]
+    fn test_best_move() {
+        let table = TableBase::new([6, 13, 15, 12, 9]);
+        let (cards, center) = card_config([6, 13, 15, 12, 9])[0];
+        let mut won = 0;
+        for my in 0..25 {
+            for other in 0..25 {
+                let mut game = Game {
+                    my: 1 << my | 1 << 2 | 2 << 25,
+                    other: 1 << other | 1 << 2 | 2 << 25,
+                    cards,
+                    table: center,
+                };
+                if game.validate().is_err() || table[game] <= Eval::new_tie() {
+                    continue;
+                }
+                won += 1;
+                // both players follow the table until the game ends
+                while !game.is_loss() {
+                    let plies = table[game].plies();
+                    let mv = table.best_move(&game).unwrap();
+                    game = game.apply(mv);
+                    assert_eq!(table[game].plies() + 1, plies);
+                }
+                assert_eq!(table[game], Eval::new_loss(0));
+                assert_eq!(table.best_move(&game), None);
+            }
+        }
+        assert!(won > 0);
+    }
+
+    #[test]
+    fn test_wdl() {
+        let table = TableBase::new([6, 13, 15, 12, 9]);
+        let wdl = WdlTableBase::from_table(&table);
+        assert_eq!(wdl.as_bytes().len() * 4, WDL_ENTRIES);
+        let built = build_wdl([6, 13, 15, 12, 9], 4);
+        let small = build_wdl([6, 13, 15, 12, 9], 3);
+        assert_eq!(small.max_pieces(), 3);
+        let mut counts = [0; 3];
+        for &(cards, center) in &card_config([6, 13, 15, 12, 9]) {
+            for (my, other) in piece_config(0) {
+                for my_king in 0..25 {
+                    let game = Game {
+                        my: 1 << my & PIECE_MASK | 1 << my_king | my_king << 25,
+                        other: 1 << 24 >> other & PIECE_MASK | 1 << 2 | 2 << 25,
+                        cards,
+                        table: center,
+                    };
+                    if game.validate().is_err() {
+                        continue;
+                    }
+                    let outcome = wdl.probe_wdl(&game);
+                    assert_eq!(outcome, Wdl::from(table[game]));
+                    assert_eq!(built.probe_wdl(&game), outcome);
+                    assert_eq!(small.covers(&game), count_pieces(&game) <= 3);
+                    if small.covers(&game) {
+                        assert_eq!(small.probe_wdl(&game), outcome);
+                    }
+                    counts[outcome as usize] += 1;
+                }
+            }
+        }
+        assert!(counts.iter().all(|&count| count > 0), "{:?}", counts);
+
+        let bytes = wdl.as_bytes().to_vec();
+        assert!(WdlTableBase::from_bytes(bytes[1..].to_vec(), 4).is_none());
+        let loaded = WdlTableBase::from_bytes(bytes, 4).unwrap();
+        assert_eq!(loaded.as_bytes(), wdl.as_bytes());
+    }
+
+    #[test]
+    fn test_search_mate() {
+        // the start has no quick win
+        assert!(!search_mate(&TEST_GAME, 3));
+        // my king on 17 steps onto the temple with ox or boar
+        let game = Game {
+            my: 1 << 17 | 17 << 25,
+            ..TEST_GAME
+        };
+        assert!(search_mate(&game, 1));
+        assert!(!search_mate(&game, 0));
+    }
+
+    #[test]
+    fn test_verify() {
+        let cards = [6, 13, 15, 12, 9];
+        let mut table = TableBase::new(cards);
+        let report = table.verify(cards, 300, &Rng::with_seed(0));
+        assert!(
+            report.discrepancies.is_empty(),
+            "{:?}",
+            report.discrepancies
+        );
+        assert!(report.checked > 0);
+
+        // a win in one stored as a win in three is reported
+        let (cards, center) = card_config(cards)[0];
+        let game = (0..25)
+            .map(|my| Game {
+                my: 1 << my | my << 25,
+                other: 1 << 2 | 2 << 25,
+                cards,
+                table: center,
+            })
+            .find(|game| game.validate().is_ok() && table[*game] == Eval::new_win(1))
+            .unwrap();
+        assert!(table.verify_game(game).is_none());
+        table[game] = Eval::new_win(2);
+        let discrepancy = table.verify_game(game).unwrap();
+        assert_eq!(discrepancy.found, Some(1));
+        assert_eq!(discrepancy.stored.plies(), 3);
+    }
 }
@@ -0,0 +1,68 @@
+use crate::{NAMES, SHIFTED, SHIFTED_L, SHIFTED_R, SHIFTED_U};
+
+pub const DUMP_MAGIC: &[u8; 4] = b"ONIT";
+pub const DUMP_VERSION: u8 = 1;
+
+/// Serializes the move tables for tools outside of Rust.
+///
+/// The layout is, with all integers little endian:
+/// - `DUMP_MAGIC` and `DUMP_VERSION`
+/// - the number of cards as a `u8`, then for each card its name length as a `u8` and the name
+/// - `SHIFTED` and `SHIFTED_R` as `u32`, then `SHIFTED_L` and `SHIFTED_U` as `u64`
+///
+/// Each table has one row of 25 squares per card, in card order.
+/// Bit `i` of an entry is square `i`, with square 0 in the corner of the player to move.
+pub fn dump_shifted_tables() -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend_from_slice(DUMP_MAGIC);
+    res.push(DUMP_VERSION);
+    res.push(NAMES.len() as u8);
+    for name in NAMES.iter() {
+        res.push(name.len() as u8);
+        res.extend_from_slice(name.as_bytes());
+    }
+    for table in &[SHIFTED, SHIFTED_R] {
+        for value in table.iter().flatten() {
+            res.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for table in &[SHIFTED_L, SHIFTED_U] {
+        for value in table.iter().flatten() {
+            res.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    #[test]
+    fn test_dump_shifted_tables() {
+        let dump = dump_shifted_tables();
+        assert_eq!(&dump[..4], DUMP_MAGIC);
+        assert_eq!(dump[4], DUMP_VERSION);
+        assert_eq!(dump[5], 16);
+
+        let mut pos = 6;
+        for name in NAMES.iter() {
+            let len = dump[pos] as usize;
+            assert_eq!(&dump[pos + 1..pos + 1 + len], name.as_bytes());
+            pos += 1 + len;
+        }
+        assert_eq!(dump.len(), pos + 2 * 16 * 25 * 4 + 2 * 16 * 25 * 8);
+
+        // ox from the center of the board
+        let ox = pos + 12 * 4;
+        let value = u32::from_le_bytes(dump[ox..ox + 4].try_into().unwrap());
+        assert_eq!(value, SHIFTED[0][12]);
+        assert_eq!(value, 1 << 17 | 1 << 11 | 1 << 7);
+        // the last entry of SHIFTED_U
+        let end = dump.len() - 8;
+        let value = u64::from_le_bytes(dump[end..].try_into().unwrap());
+        assert_eq!(value, SHIFTED_U[15][24]);
+    }
+}
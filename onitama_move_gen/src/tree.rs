@@ -0,0 +1,275 @@
+use crate::{gen::Game, moves::Move};
+
+pub type NodeId = usize;
+
+#[derive(Clone, Debug)]
+pub struct TreeNode {
+    pub game: Game,
+    /// The move that reached this node, `None` for the root.
+    pub mv: Option<Move>,
+    pub parent: Option<NodeId>,
+    /// Cached result of a search, the tree does not compute it.
+    pub eval: Option<i8>,
+    children: Vec<NodeId>,
+    expanded: bool,
+}
+
+impl TreeNode {
+    fn new(game: Game, mv: Option<Move>, parent: Option<NodeId>) -> Self {
+        Self {
+            game,
+            mv,
+            parent,
+            eval: None,
+            children: Vec::new(),
+            expanded: false,
+        }
+    }
+
+    /// The children added so far, all of them after `GameTree::expand`.
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn is_expanded(&self) -> bool {
+        self.expanded
+    }
+}
+
+/// A game with variations for analysis, navigated with a current node.
+/// Nodes are kept in one list and removed nodes leave a hole, so a `NodeId` stays valid
+/// until its node is pruned.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    nodes: Vec<Option<TreeNode>>,
+    current: NodeId,
+}
+
+impl GameTree {
+    pub fn new(root: Game) -> Self {
+        Self {
+            nodes: vec![Some(TreeNode::new(root, None, None))],
+            current: 0,
+        }
+    }
+
+    #[inline]
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    #[inline]
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// Panics if the node was pruned.
+    pub fn node(&self, id: NodeId) -> &TreeNode {
+        self.nodes[id].as_ref().expect("node was pruned")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut TreeNode {
+        self.nodes[id].as_mut().expect("node was pruned")
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&TreeNode> {
+        self.nodes.get(id)?.as_ref()
+    }
+
+    /// Number of nodes that were not pruned.
+    pub fn len(&self) -> usize {
+        self.nodes.iter().filter(|node| node.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn child_with_move(&self, id: NodeId, mv: Move) -> Option<NodeId> {
+        let node = self.node(id);
+        node.children
+            .iter()
+            .copied()
+            .find(|&child| self.node(child).mv == Some(mv))
+    }
+
+    fn push(&mut self, game: Game, mv: Move, parent: NodeId) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes
+            .push(Some(TreeNode::new(game, Some(mv), Some(parent))));
+        self.node_mut(parent).children.push(id);
+        id
+    }
+
+    /// Adds all children of `id` in the order of `forward`, keeping the variations that exist.
+    /// Finished games have no children.
+    pub fn expand(&mut self, id: NodeId) -> &[NodeId] {
+        if !self.node(id).expanded {
+            let game = self.node(id).game;
+            let mut children = Vec::new();
+            if !game.is_loss() {
+                for (mv, new_game) in game.forward_with_moves() {
+                    let child = match self.child_with_move(id, mv) {
+                        Some(child) => child,
+                        None => {
+                            let child = self.nodes.len();
+                            self.nodes
+                                .push(Some(TreeNode::new(new_game, Some(mv), Some(id))));
+                            child
+                        }
+                    };
+                    children.push(child);
+                }
+            }
+            let node = self.node_mut(id);
+            node.children = children;
+            node.expanded = true;
+        }
+        &self.node(id).children
+    }
+
+    /// The child of `id` reached with `mv`, which is added if it is not there yet.
+    /// Returns `None` if the move is not legal.
+    pub fn add_variation(&mut self, id: NodeId, mv: Move) -> Option<NodeId> {
+        if let Some(child) = self.child_with_move(id, mv) {
+            return Some(child);
+        }
+        let game = self.node(id).game;
+        if game.is_loss() || !game.legal_moves_small().contains(&mv) {
+            return None;
+        }
+        Some(self.push(game.apply(mv), mv, id))
+    }
+
+    /// Plays `mv` from the current node, adding it as a variation if needed.
+    pub fn play(&mut self, mv: Move) -> Option<NodeId> {
+        let child = self.add_variation(self.current, mv)?;
+        self.current = child;
+        Some(child)
+    }
+
+    /// Goes to the parent of the current node, `false` at the root.
+    pub fn back(&mut self) -> bool {
+        match self.node(self.current).parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes `id` the current node, `false` if it was pruned.
+    pub fn goto(&mut self, id: NodeId) -> bool {
+        let exists = self.get(id).is_some();
+        if exists {
+            self.current = id;
+        }
+        exists
+    }
+
+    /// The cached evaluation of `id`, computed with `eval` the first time.
+    pub fn eval_with(&mut self, id: NodeId, eval: impl FnOnce(&Game) -> i8) -> i8 {
+        let node = self.node_mut(id);
+        match node.eval {
+            Some(value) => value,
+            None => {
+                let value = eval(&node.game);
+                node.eval = Some(value);
+                value
+            }
+        }
+    }
+
+    /// Removes `id` and everything below it. The root can not be pruned.
+    /// If the current node is removed, the parent of `id` becomes the current node.
+    pub fn prune(&mut self, id: NodeId) -> bool {
+        let parent = match self.get(id).and_then(|node| node.parent) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        let parent_node = self.node_mut(parent);
+        parent_node.children.retain(|&child| child != id);
+        parent_node.expanded = false;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id].take() {
+                stack.extend(node.children);
+                if id == self.current {
+                    self.current = parent;
+                }
+            }
+        }
+        true
+    }
+
+    /// Moves from the root to the current node.
+    pub fn line(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut id = self.current;
+        while let Some(node) = self.get(id) {
+            match (node.mv, node.parent) {
+                (Some(mv), Some(parent)) => {
+                    moves.push(mv);
+                    id = parent;
+                }
+                _ => break,
+            }
+        }
+        moves.reverse();
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_game_tree() {
+        let mut tree = GameTree::new(TEST_GAME);
+        assert_eq!(tree.len(), 1);
+        assert!(!tree.back());
+
+        let moves = TEST_GAME.legal_moves_vec();
+        let first = tree.play(moves[3]).unwrap();
+        assert_eq!(tree.node(first).game, TEST_GAME.apply(moves[3]));
+        let reply = tree.node(first).game.legal_moves_vec()[0];
+        tree.play(reply).unwrap();
+        assert_eq!(tree.line(), vec![moves[3], reply]);
+        // an illegal move is not added
+        assert_eq!(tree.play(moves[3]), None);
+
+        // expanding keeps the variation that was played
+        let children = tree.expand(0).to_vec();
+        assert_eq!(children.len(), moves.len());
+        assert_eq!(children[3], first);
+        for (&child, &mv) in children.iter().zip(moves.iter()) {
+            assert_eq!(tree.node(child).mv, Some(mv));
+            assert_eq!(tree.node(child).game, TEST_GAME.apply(mv));
+        }
+        assert_eq!(tree.len(), 1 + moves.len() + 1);
+        assert_eq!(tree.add_variation(0, moves[3]), Some(first));
+
+        // the evaluation is only computed once
+        let mut calls = 0;
+        for _ in 0..2 {
+            let eval = tree.eval_with(first, |game| {
+                calls += 1;
+                game.count_moves() as i8
+            });
+            assert_eq!(eval, tree.node(first).game.count_moves() as i8);
+        }
+        assert_eq!(calls, 1);
+
+        // pruning the played line goes back to the root
+        assert!(tree.prune(first));
+        assert_eq!(tree.current(), 0);
+        assert_eq!(tree.len(), moves.len());
+        assert!(tree.get(first).is_none());
+        assert!(!tree.goto(first));
+        assert!(!tree.prune(0));
+        assert_eq!(tree.expand(0).len(), moves.len());
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use crate::{
+    card::{Card, Color},
+    gen::{Game, PIECE_MASK},
+    ops::BitIter,
+};
+
+fn card_names(hand: u32) -> String {
+    let names: Vec<String> = BitIter(hand & 0xffff)
+        .map(|card| format!("\"{}\"", Card(card).name()))
+        .collect();
+    format!("[{}]", names.join(","))
+}
+
+impl Game {
+    /// The game as JSON for a board in a web page, with every square in the frame of blue.
+    /// `board` lists the pieces by square as `{"square", "owner", "is_king"}`,
+    /// the cards are given by name and `my_cards` are those of `to_move`,
+    /// which the game does not know, like in `display_for`.
+    pub fn to_web_json(&self, to_move: Color) -> String {
+        let other_color = to_move.opponent();
+        // squares in the frame of their owner
+        let sides = [(self.my, to_move), (self.other, other_color)];
+        let mut pieces = Vec::with_capacity(10);
+        for &(side, color) in &sides {
+            for pos in BitIter(side & PIECE_MASK) {
+                let square = match color {
+                    Color::Blue => pos,
+                    Color::Red => 24 - pos,
+                };
+                pieces.push((square, color, pos == side.wrapping_shr(25)));
+            }
+        }
+        pieces.sort_unstable_by_key(|&(square, _, _)| square);
+
+        let mut res = String::from("{\"board\":[");
+        for (i, &(square, owner, is_king)) in pieces.iter().enumerate() {
+            if i > 0 {
+                res.push(',');
+            }
+            write!(
+                res,
+                "{{\"square\":{},\"owner\":\"{}\",\"is_king\":{}}}",
+                square,
+                owner.name(),
+                is_king
+            )
+            .unwrap();
+        }
+        write!(
+            res,
+            "],\"my_cards\":{},\"other_cards\":{},\"table\":\"{}\",\"to_move\":\"{}\"}}",
+            card_names(self.cards),
+            card_names(self.cards.wrapping_shr(16)),
+            Card(self.table).name(),
+            to_move.name()
+        )
+        .unwrap();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::TEST_GAME;
+
+    #[test]
+    fn test_to_web_json() {
+        let json = TEST_GAME.to_web_json(Color::Red);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let board = value["board"].as_array().unwrap();
+        assert_eq!(board.len(), 10);
+        assert_eq!(board.iter().filter(|p| p["is_king"] == true).count(), 2);
+        // red moves, so the pieces of red are on the far side in the frame of blue
+        assert_eq!(board[0]["owner"], "blue");
+        assert_eq!(board[9]["owner"], "red");
+        assert_eq!(
+            board[2],
+            serde_json::json!({"square": 2, "owner": "blue", "is_king": true})
+        );
+        assert_eq!(value["my_cards"], serde_json::json!(["ox", "boar"]));
+        assert_eq!(
+            value["other_cards"],
+            serde_json::json!(["horse", "elephant"])
+        );
+        assert_eq!(value["table"], "crab");
+        assert_eq!(value["to_move"], "red");
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    gen::{Game, PIECE_MASK},
+    ops::{BitIter, SplitMix64},
+};
+
+pub struct ZobristKeys {
+    pub pieces: [u64; 25],
+    pub kings: [u64; 25],
+    pub cards: [u64; 16],
+    pub table: [u64; 16],
+}
+
+impl ZobristKeys {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = SplitMix64(seed);
+        let mut next = || rng.next();
+        let mut keys = ZobristKeys {
+            pieces: [0; 25],
+            kings: [0; 25],
+            cards: [0; 16],
+            table: [0; 16],
+        };
+        keys.pieces.iter_mut().for_each(|k| *k = next());
+        keys.kings.iter_mut().for_each(|k| *k = next());
+        keys.cards.iter_mut().for_each(|k| *k = next());
+        keys.table.iter_mut().for_each(|k| *k = next());
+        keys
+    }
+
+    #[inline]
+    fn side(&self, pieces: u32, cards: u32) -> u64 {
+        let mut hash = self.kings[pieces.wrapping_shr(25) as usize];
+        for pos in BitIter(pieces & PIECE_MASK) {
+            hash ^= self.pieces[pos as usize];
+        }
+        for card in BitIter(cards & 0xffff) {
+            hash ^= self.cards[card as usize];
+        }
+        hash
+    }
+}
+
+impl Game {
+    /// Both sides are hashed in their own frame, the other side rotated by 32 bits.
+    /// Rotating by 32 is an involution, so a child hash follows from the parent hash
+    /// by rotating and xoring in the pieces and cards that changed.
+    /// The hash only depends on the position, so transpositions get the same hash.
+    #[inline]
+    pub fn zobrist_hash(&self, keys: &ZobristKeys) -> u64 {
+        keys.side(self.my, self.cards)
+            ^ keys
+                .side(self.other, self.cards.wrapping_shr(16))
+                .rotate_left(32)
+            ^ keys.table[self.table as usize]
+    }
+
+    /// Multiply-xor mix of all four fields for pre-filtering duplicates in datasets.
+    /// Not cryptographic and not incremental like `zobrist_hash`,
+    /// collisions are possible so equal hashes still need an exact comparison.
+    #[inline]
+    pub fn fast_hash(&self) -> u64 {
+        const K: u64 = 0x9e37_79b9_7f4a_7c15;
+        let pieces = (self.my as u64) << 32 | self.other as u64;
+        let cards = (self.cards as u64) << 32 | self.table as u64;
+        let mut hash = pieces.wrapping_mul(K) ^ cards;
+        hash = hash.wrapping_mul(K);
+        hash ^ hash >> 32
+    }
+
+    #[inline]
+    pub fn children_with_hash<'a>(
+        &'a self,
+        keys: &'a ZobristKeys,
+        hash: u64,
+    ) -> impl Iterator<Item = (Game, u64)> + 'a {
+        let base = (hash ^ keys.table[self.table as usize]).rotate_left(32);
+        self.forward().map(move |new_game| {
+            let moved = (self.my ^ new_game.other) & PIECE_MASK;
+            let from = (moved & self.my).trailing_zeros();
+            let to = (moved & !self.my).trailing_zeros();
+
+            let mut my_diff = keys.pieces[from as usize]
+                ^ keys.pieces[to as usize]
+                ^ keys.cards[new_game.table as usize]
+                ^ keys.cards[self.table as usize];
+            if from == self.my.wrapping_shr(25) {
+                my_diff ^= keys.kings[from as usize] ^ keys.kings[to as usize];
+            }
+            let mut other_diff = keys.table[new_game.table as usize];
+            if self.other & 1 << 24 >> to != 0 {
+                other_diff ^= keys.pieces[24 - to as usize];
+            }
+
+            (new_game, base ^ other_diff ^ my_diff.rotate_left(32))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{moves::Move, perft::TEST_GAME};
+
+    fn check_children(game: Game, keys: &ZobristKeys, depth: u8) {
+        let hash = game.zobrist_hash(keys);
+        assert_eq!(
+            game.forward().count(),
+            game.children_with_hash(keys, hash).count()
+        );
+        for (new_game, new_hash) in game.children_with_hash(keys, hash) {
+            assert_eq!(new_hash, new_game.zobrist_hash(keys));
+            if depth > 1 && !new_game.is_loss() {
+                check_children(new_game, keys, depth - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_children_with_hash() {
+        let keys = ZobristKeys::new(0);
+        check_children(TEST_GAME, &keys, 4);
+    }
+
+    fn collect_lines(
+        game: Game,
+        hash: u64,
+        keys: &ZobristKeys,
+        depth: u8,
+        seen: &mut HashMap<u128, u64>,
+    ) -> usize {
+        if depth == 0 {
+            let seen_hash = *seen.entry(game.repetition_key()).or_insert(hash);
+            assert_eq!(seen_hash, hash);
+            assert_eq!(hash, game.zobrist_hash(keys));
+            return 1;
+        }
+        if game.is_loss() {
+            return 0;
+        }
+        game.children_with_hash(keys, hash)
+            .map(|(new_game, new_hash)| collect_lines(new_game, new_hash, keys, depth - 1, seen))
+            .sum()
+    }
+
+    #[test]
+    fn test_transpositions() {
+        // the same position reached by different lines gets the same hash,
+        // also when it is updated along the line with `children_with_hash`
+        let keys = ZobristKeys::new(0);
+        let mut seen = HashMap::new();
+        let lines = collect_lines(
+            TEST_GAME,
+            TEST_GAME.zobrist_hash(&keys),
+            &keys,
+            4,
+            &mut seen,
+        );
+        assert!(seen.len() < lines);
+
+        // both pawns go forward and back, which brings the cards back to where they started
+        let game = Game {
+            my: 1 << 0 | 1 << 5,
+            other: 1 << 0 | 1 << 10,
+            cards: (1 << 5 | 1 << 1) | (1 << 2 | 1 << 3) << 16,
+            table: 0,
+        };
+        let start = game.zobrist_hash(&keys);
+        let (mut new_game, mut hash) = (game, start);
+        let cycle = [(5, 15, 5), (10, 5, 2), (15, 10, 0)];
+        for &(from, to, card) in cycle.iter().chain(cycle.iter()) {
+            let child = new_game.apply(Move { from, to, card });
+            hash = new_game
+                .children_with_hash(&keys, hash)
+                .find(|&(g, _)| g == child)
+                .unwrap()
+                .1;
+            new_game = child;
+        }
+        assert_eq!(new_game, game);
+        assert_eq!(hash, start);
+    }
+
+    #[test]
+    fn test_side_to_move() {
+        let keys = ZobristKeys::new(0);
+        let game = Game {
+            my: 0b11 | 1 << 25,
+            other: 0b111 | 2 << 25,
+            ..TEST_GAME
+        };
+        let swapped = Game {
+            my: game.other,
+            other: game.my,
+            cards: game.cards.rotate_left(16),
+            table: game.table,
+        };
+        assert_ne!(game.zobrist_hash(&keys), swapped.zobrist_hash(&keys));
+    }
+
+    #[test]
+    fn test_fast_hash() {
+        let mut games = vec![TEST_GAME];
+        for new_game in TEST_GAME.forward() {
+            games.extend(new_game.forward());
+        }
+        let mut hashes: Vec<u64> = games.iter().map(Game::fast_hash).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), games.len());
+
+        let swapped = Game {
+            my: TEST_GAME.other,
+            other: TEST_GAME.my,
+            cards: TEST_GAME.cards.rotate_left(16),
+            table: TEST_GAME.table,
+        };
+        assert_ne!(TEST_GAME.fast_hash(), swapped.fast_hash());
+    }
+}
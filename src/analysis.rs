@@ -0,0 +1,363 @@
+use fastrand::Rng;
+use onitama_move_gen::{gen::Game, moves::Move, perft::perft};
+
+use crate::{
+    evaluator::{mate_in, parent_score, MATE_BOUND},
+    node::Agent,
+};
+
+/// Scores every child of `game` with a search of `depth` plies in total.
+/// The result is sorted from best to worst for the player to move.
+pub fn multi_pv(agent: &Agent, game: Game, depth: u8) -> Option<Vec<(Game, i32)>> {
+    let mut scores = Vec::new();
+    for new_game in game.forward() {
+        let mut node = agent.new_node(new_game, 0);
+        if depth > 1 && !node.is_table() {
+            loop {
+                agent.bns(&mut node)?;
+                let lower = node.get_lower();
+                if node.get_depth() >= depth - 1 || mate_in(lower).is_some() {
+                    break;
+                }
+            }
+        }
+        scores.push((new_game, parent_score(node.get_lower())));
+    }
+    scores.sort_by_key(|&(_, score)| -score);
+    Some(scores)
+}
+
+/// Scores of the `played` move and of the best move with a search of `depth` plies,
+/// so a review can flag a blunder by the gap between them.
+/// Returns `None` if the move is not legal or the search runs out of memory.
+pub fn evaluate_move(agent: &Agent, game: Game, played: Move, depth: u8) -> Option<(i32, i32)> {
+    if game.is_loss() || !game.legal_moves_small().contains(&played) {
+        return None;
+    }
+    let played = game.apply(played);
+    let scores = multi_pv(agent, game, depth)?;
+    let &(_, score) = scores.iter().find(|&&(new_game, _)| new_game == played)?;
+    Some((score, scores[0].1))
+}
+
+/// How much better the best move is than the second best, between 0 and 1.
+/// A winning move is maximally sharp when the second best move does not win.
+/// Positions with a single legal move are maximally sharp too.
+pub fn difficulty(agent: &Agent, game: Game, depth: u8) -> Option<f32> {
+    let scores = multi_pv(agent, game, depth)?;
+    Some(match scores.as_slice() {
+        [] | [_] => 1.0,
+        [(_, best), (_, second), ..] => {
+            if *best > MATE_BOUND && *second <= MATE_BOUND {
+                1.0
+            } else {
+                gap(*best, *second)
+            }
+        }
+    })
+}
+
+/// Difference of two scores between 0 and 1, wins and losses count like the largest
+/// heuristic scores.
+fn gap(best: i32, second: i32) -> f32 {
+    let clamp = |score: i32| score.clamp(-MATE_BOUND - 1, MATE_BOUND + 1) as f32;
+    (clamp(best) - clamp(second)) / (2 * (MATE_BOUND + 1)) as f32
+}
+
+/// Difficulty of finding the best move of `game` between 0 and 1, for rating puzzles.
+/// It is the mean of three parts, each between 0 and 1:
+/// - the number of legal moves divided by 40, the most there can be,
+/// - `1 - difficulty`, so a small gap between the best and second best move is hard,
+///   except that a single winning move counts as 1,
+/// - `(d - 1) / depth` for the depth `d` from which `multi_pv` keeps the same best move.
+pub fn complexity(agent: &Agent, game: Game, depth: u8) -> Option<f32> {
+    assert!(depth > 0, "depth has to be at least 1");
+    let mut iterations = Vec::with_capacity(depth as usize);
+    for d in 1..=depth {
+        iterations.push(multi_pv(agent, game, d)?);
+    }
+    let scores = iterations.last().unwrap();
+    let moves = scores.len() as f32 / 40.0;
+    let spread = match scores.as_slice() {
+        [] | [_] => 0.0,
+        [(_, first), (_, second), ..] => {
+            if *first > MATE_BOUND && *second <= MATE_BOUND {
+                1.0
+            } else {
+                1.0 - gap(*first, *second)
+            }
+        }
+    };
+    let best_game = scores.first().map(|&(new_game, _)| new_game);
+    let stable = iterations
+        .iter()
+        .rposition(|scores| scores.first().map(|&(new_game, _)| new_game) != best_game)
+        .map_or(0, |i| i + 1);
+    let stability = stable as f32 / depth as f32;
+    Some((moves + spread + stability) / 3.0)
+}
+
+/// A child of `game` for varied self-play, sampled from the moves that `multi_pv` scores
+/// within `margin` of the best with weights `exp((score - best) / diversity)`.
+/// At `diversity = 0` this is the best move, and a winning move is always played.
+pub fn diverse_move(
+    agent: &Agent,
+    game: Game,
+    depth: u8,
+    diversity: f32,
+    margin: i32,
+    rng: &Rng,
+) -> Option<Game> {
+    let scores = multi_pv(agent, game, depth)?;
+    let &(best_game, best) = scores.first()?;
+    if diversity <= 0.0 || best > MATE_BOUND {
+        return Some(best_game);
+    }
+    let weights: Vec<(Game, f32)> = scores
+        .iter()
+        .take_while(|&&(_, score)| best - score <= margin)
+        .map(|&(new_game, score)| (new_game, ((score as f32 - best as f32) / diversity).exp()))
+        .collect();
+    let mut target = rng.f32() * weights.iter().map(|&(_, weight)| weight).sum::<f32>();
+    for &(new_game, weight) in &weights {
+        if target < weight {
+            return Some(new_game);
+        }
+        target -= weight;
+    }
+    Some(best_game)
+}
+
+/// Ratio of the perft counts at `depth` and `depth - 1` plies.
+pub fn average_branching_factor(game: &Game, depth: u8) -> f32 {
+    assert!(depth > 0, "depth has to be at least 1");
+    perft(*game, depth) as f32 / perft(*game, depth - 1) as f32
+}
+
+/// Number of positions `depth` plies below `game` by the number of pieces of both players.
+/// Finished games are counted where they end like in `perft`, so the counts add up to it.
+pub fn material_histogram(game: &Game, depth: u8) -> [u64; 11] {
+    fn walk(game: &Game, depth: u8, histogram: &mut [u64; 11]) {
+        if depth == 0 || game.is_loss() {
+            histogram[game.count_pieces() + game.count_other_pieces()] += 1;
+            return;
+        }
+        for new_game in game.forward() {
+            walk(&new_game, depth - 1, histogram);
+        }
+    }
+    let mut histogram = [0; 11];
+    walk(game, depth, &mut histogram);
+    histogram
+}
+
+/// Whether the player to move can force a win with at most `moves` of their own moves.
+fn wins_within(game: &Game, moves: u8) -> bool {
+    if moves == 0 {
+        return false;
+    }
+    if game.is_win() {
+        return true;
+    }
+    moves > 1
+        && game
+            .forward()
+            .any(|new_game| forced_after(&new_game, moves - 1))
+}
+
+/// Whether every reply of the other player in `game` still loses within `moves` moves.
+fn forced_after(game: &Game, moves: u8) -> bool {
+    game.is_loss()
+        || moves > 0
+            && !game.is_win()
+            && game.forward().all(|new_game| wins_within(&new_game, moves))
+}
+
+/// Number of first moves that force a win with at most `moves` moves of the player to move,
+/// found by a full search without evaluation. A puzzle has a unique solution if this is 1.
+pub fn count_winning_lines(game: &Game, moves: u8) -> usize {
+    if moves == 0 || game.is_loss() {
+        return 0;
+    }
+    game.forward()
+        .filter(|new_game| forced_after(new_game, moves - 1))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use onitama_move_gen::{perft::TEST_GAME, tablebase::TableBase};
+
+    use super::*;
+    use crate::evaluator::{HandcraftedEvaluator, MATE};
+
+    // tiger takes the other king from 7 to 17
+    const MATE_IN_ONE: Game = Game {
+        my: 1 << 2 | 1 << 7 | 2 << 25,
+        other: 1 << 7 | 7 << 25,
+        cards: (1 << 5 | 1 << 2) | (1 << 0 | 1 << 4) << 16,
+        table: 14,
+    };
+
+    #[test]
+    fn test_multi_pv() {
+        let agent = Agent::new(TableBase::empty().into());
+        let scores = multi_pv(&agent, MATE_IN_ONE, 2).unwrap();
+        assert_eq!(scores.len(), MATE_IN_ONE.forward().count());
+        assert_eq!(scores[0].1, MATE - 1);
+        assert!(scores[0].0.is_loss());
+        assert!(scores[1].1 < MATE - 1);
+    }
+
+    #[test]
+    fn test_evaluate_move() {
+        let agent = Agent::new(TableBase::empty().into());
+        let take = Move {
+            from: 7,
+            to: 17,
+            card: 5,
+        };
+        assert_eq!(
+            evaluate_move(&agent, MATE_IN_ONE, take, 2),
+            Some((MATE - 1, MATE - 1))
+        );
+        // the king steps aside instead of winning
+        let blunder = Move {
+            from: 2,
+            to: 3,
+            card: 2,
+        };
+        let (played, best) = evaluate_move(&agent, MATE_IN_ONE, blunder, 2).unwrap();
+        assert_eq!(best, MATE - 1);
+        assert!(best - played > 100);
+        // horse can not move sideways
+        let illegal = Move { card: 2, ..take };
+        assert_eq!(evaluate_move(&agent, MATE_IN_ONE, illegal, 2), None);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let agent = Agent::new(TableBase::empty().into());
+        assert_eq!(difficulty(&agent, MATE_IN_ONE, 2), Some(1.0));
+        let start = difficulty(&agent, TEST_GAME, 2).unwrap();
+        assert!((0.0..1.0).contains(&start));
+    }
+
+    #[test]
+    fn test_complexity() {
+        let agent = Agent::new(TableBase::empty().into());
+        // the only winning move is found at the first depth
+        let moves = MATE_IN_ONE.count_moves() as f32;
+        let mate = complexity(&agent, MATE_IN_ONE, 2).unwrap();
+        assert!((mate - (moves / 40.0 + 1.0) / 3.0).abs() < 1e-6);
+        for depth in 1..=3 {
+            let start = complexity(&agent, TEST_GAME, depth).unwrap();
+            assert!((0.0..=1.0).contains(&start));
+        }
+    }
+
+    #[test]
+    fn test_diverse_move() {
+        let agent = Agent::with_evaluator(Rc::new(HandcraftedEvaluator::default()));
+        let rng = Rng::with_seed(3);
+        let distinct = |diversity: f32| {
+            let mut ends = Vec::new();
+            for _ in 0..8 {
+                let mut game = TEST_GAME;
+                for _ in 0..6 {
+                    game = diverse_move(&agent, game, 1, diversity, 40, &rng).unwrap();
+                }
+                if !ends.contains(&game) {
+                    ends.push(game);
+                }
+            }
+            ends.len()
+        };
+        assert_eq!(distinct(0.0), 1);
+        assert!(distinct(20.0) > 1);
+
+        // the winning move is played however diverse
+        for _ in 0..10 {
+            let new_game = diverse_move(&agent, MATE_IN_ONE, 1, 100.0, 127, &rng).unwrap();
+            assert!(new_game.is_loss());
+        }
+    }
+
+    #[test]
+    fn test_material_histogram() {
+        // nothing can be taken in the first two plies
+        assert_eq!(
+            material_histogram(&TEST_GAME, 1)[10],
+            TEST_GAME.count_moves()
+        );
+        assert_eq!(material_histogram(&TEST_GAME, 2)[10], perft(TEST_GAME, 2));
+        for depth in 2..=4 {
+            let histogram = material_histogram(&TEST_GAME, depth);
+            assert_eq!(histogram.iter().sum::<u64>(), perft(TEST_GAME, depth));
+        }
+        let histogram = material_histogram(&TEST_GAME, 4);
+        assert!(histogram[9] > 0);
+    }
+
+    #[test]
+    fn test_count_winning_lines() {
+        assert_eq!(
+            count_winning_lines(&MATE_IN_ONE, 1),
+            MATE_IN_ONE.winning_moves().len()
+        );
+        assert_eq!(count_winning_lines(&MATE_IN_ONE, 0), 0);
+
+        // only crab from 11 to 16 wins in two moves
+        let unique = Game {
+            my: 1 << 3 | 1 << 5 | 1 << 11 | 11 << 25,
+            other: 1 << 1 | 1 << 3 | 1 << 14 | 1 << 16 | 1 << 25,
+            cards: (1 << 4 | 1 << 9) | (1 << 3 | 1 << 5) << 16,
+            table: 0,
+        };
+        assert_eq!(count_winning_lines(&unique, 1), 0);
+        assert_eq!(count_winning_lines(&unique, 2), 1);
+        let solution = Move {
+            from: 11,
+            to: 16,
+            card: 4,
+        };
+        let (_, new_game) = unique
+            .forward_with_moves()
+            .find(|&(mv, _)| mv == solution)
+            .unwrap();
+        assert!(forced_after(&new_game, 1));
+
+        // dragon from 11 to 18 and goose from 11 to 12 both win
+        let two = Game {
+            my: 1 << 8 | 1 << 10 | 1 << 11 | 10 << 25,
+            other: 1 << 1 | 1 << 4 | 1 << 5 | 1 << 11 | 5 << 25,
+            cards: (1 << 8 | 1 << 12) | (1 << 11 | 1 << 14) << 16,
+            table: 3,
+        };
+        assert_eq!(count_winning_lines(&two, 1), 0);
+        assert_eq!(count_winning_lines(&two, 2), 2);
+        let agent = Agent::new(TableBase::empty().into());
+        let scores = multi_pv(&agent, two, 3).unwrap();
+        assert_eq!(
+            scores
+                .iter()
+                .filter(|&&(_, score)| mate_in(score) == Some(3))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_average_branching_factor() {
+        assert_eq!(
+            average_branching_factor(&TEST_GAME, 1),
+            TEST_GAME.count_moves() as f32
+        );
+        for depth in 2..=4 {
+            let factor = average_branching_factor(&TEST_GAME, depth);
+            assert!((6.0..=16.0).contains(&factor), "{}", factor);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::rc::Rc;
+
+use onitama_move_gen::{
+    eval::temple_control,
+    gen::{Game, Player},
+    tablebase::TableBase,
+};
+
+/// Largest score of a position that is not decided, heuristic scores are clamped to it.
+pub const MATE_BOUND: i32 = 126;
+
+/// Longest distance to mate in plies that scores are told apart by.
+pub const MAX_MATE_PLIES: i32 = 255;
+
+/// Score of a lost position, `-MATE`. A position that is won in `n` plies scores `MATE - n`
+/// and one that is lost in `n` plies `-(MATE - n)`, so quicker wins score higher,
+/// see `mate_in`. Scores stay far from `i32::MIN`, so they can always be negated.
+pub const MATE: i32 = MATE_BOUND + 1 + MAX_MATE_PLIES;
+
+/// Plies to the end of the game for the score of a won or lost position,
+/// `None` for the score of a position that is not decided.
+#[inline]
+pub fn mate_in(score: i32) -> Option<u8> {
+    if score.abs() > MATE_BOUND {
+        Some((MATE - score.abs()) as u8)
+    } else {
+        None
+    }
+}
+
+/// Score of a position for the score `score` of the position after one of its moves.
+/// The end of the game is one ply further away, so won and lost scores move towards zero.
+#[inline(always)]
+pub fn parent_score(score: i32) -> i32 {
+    let score = -score;
+    if score > MATE_BOUND {
+        score - 1
+    } else if score < -MATE_BOUND {
+        score + 1
+    } else {
+        score
+    }
+}
+
+/// Scores a position for the player to move between `-MATE` and `MATE`.
+/// The flag is set when the score is exact and the position does not need to be searched.
+pub trait Evaluator {
+    fn eval(&self, game: &Game) -> (bool, i32);
+}
+
+/// Covered positions get the distance to mate of the table, see `mate_in`.
+impl Evaluator for TableBase {
+    #[inline]
+    fn eval(&self, game: &Game) -> (bool, i32) {
+        let (exact, value) = TableBase::eval(self, *game);
+        if exact && value != 0 {
+            let plies = self[*game].plies() as i32;
+            (true, value.signum() as i32 * (MATE - plies))
+        } else {
+            (exact, value as i32)
+        }
+    }
+}
+
+/// Material, king advancement and control of the temples, for when there is no tablebase.
+#[derive(Clone, Copy, Debug)]
+pub struct HandcraftedEvaluator {
+    pub pawn: i8,
+    pub king_distance: i8,
+    pub temple: i8,
+}
+
+impl Default for HandcraftedEvaluator {
+    fn default() -> Self {
+        Self {
+            pawn: 40,
+            king_distance: 5,
+            temple: 2,
+        }
+    }
+}
+
+impl HandcraftedEvaluator {
+    #[inline]
+    pub fn evaluate_with(&self, game: &Game) -> i32 {
+        let pawns = game.count_pieces() as i32 - game.count_other_pieces() as i32;
+        let distance = game.king_distance_to_temple(Player::Other) as i32
+            - game.king_distance_to_temple(Player::My) as i32;
+        let score = pawns * self.pawn as i32
+            + distance * self.king_distance as i32
+            + temple_control(game) * self.temple as i32;
+        score.clamp(-MATE_BOUND, MATE_BOUND)
+    }
+}
+
+impl Evaluator for HandcraftedEvaluator {
+    #[inline]
+    fn eval(&self, game: &Game) -> (bool, i32) {
+        (false, self.evaluate_with(game))
+    }
+}
+
+/// Probes the tablebase for the positions it solves and falls back to `evaluator` otherwise.
+/// The search stops at exact scores, so covered positions are never searched further.
+pub struct TableBaseFallback<E> {
+    pub tablebase: Rc<TableBase>,
+    pub evaluator: E,
+}
+
+impl<E: Evaluator> Evaluator for TableBaseFallback<E> {
+    #[inline]
+    fn eval(&self, game: &Game) -> (bool, i32) {
+        if TableBase::covers(game) {
+            Evaluator::eval(&*self.tablebase, game)
+        } else {
+            self.evaluator.eval(game)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        node::Agent,
+        protocol::{best_move, Limit},
+    };
+
+    #[test]
+    fn test_mate_in() {
+        assert_eq!(mate_in(-MATE), Some(0));
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(-(MATE - 4)), Some(4));
+        assert_eq!(mate_in(MATE_BOUND), None);
+        assert_eq!(mate_in(0), None);
+        // a loss now is a win in one for the player before
+        assert_eq!(parent_score(-MATE), MATE - 1);
+        assert_eq!(parent_score(MATE - 1), -(MATE - 2));
+        assert_eq!(parent_score(MATE_BOUND), -MATE_BOUND);
+    }
+
+    #[test]
+    fn test_evaluate_with() {
+        let evaluator = HandcraftedEvaluator::default();
+        let game = Game {
+            my: 1 << 7 | 1 << 11 | 7 << 25,
+            other: 1 << 0 | 1 << 8,
+            cards: (1 << 0 | 1 << 1) | (1 << 5 | 1 << 7) << 16,
+            table: 3,
+        };
+        // my king is one step closer and I control one more square around the temples
+        assert_eq!(temple_control(&game), 1);
+        assert_eq!(evaluator.evaluate_with(&game), 7);
+        let game = Game {
+            other: 1 << 0,
+            ..game
+        };
+        // without the pawn on 8 the other player does not reach the temples
+        assert_eq!(temple_control(&game), 5);
+        assert_eq!(evaluator.evaluate_with(&game), 55);
+    }
+
+    #[test]
+    fn test_different_best_moves() {
+        // the pawn on 11 can take on 16, or the king on 7 can advance to 12
+        let game = Game {
+            my: 1 << 7 | 1 << 11 | 7 << 25,
+            other: 1 << 0 | 1 << 8,
+            cards: (1 << 0 | 1 << 1) | (1 << 5 | 1 << 7) << 16,
+            table: 3,
+        };
+        let material = HandcraftedEvaluator {
+            pawn: 40,
+            king_distance: 0,
+            temple: 0,
+        };
+        let advance = HandcraftedEvaluator {
+            pawn: 0,
+            king_distance: 5,
+            temple: 0,
+        };
+
+        let take = best_move(Rc::new(material), game, Limit::Depth(1)).unwrap();
+        assert_eq!(take.count_pieces(), 1);
+
+        let step = best_move(Rc::new(advance), game, Limit::Depth(1)).unwrap();
+        assert_eq!(step.count_pieces(), 2);
+        assert_eq!(step.king_distance_to_temple(Player::Other), 2);
+    }
+
+    #[test]
+    fn test_tablebase_fallback() {
+        let tablebase: Rc<TableBase> = TableBase::empty().into();
+        let evaluator = HandcraftedEvaluator::default();
+        let fallback = TableBaseFallback {
+            tablebase: tablebase.clone(),
+            evaluator,
+        };
+        // kings and one pawn each
+        let covered = Game {
+            my: 1 << 2 | 1 << 6 | 2 << 25,
+            other: 1 << 2 | 1 << 8 | 2 << 25,
+            cards: (1 << 0 | 1 << 1) | (1 << 2 | 1 << 3) << 16,
+            table: 4,
+        };
+        assert!(TableBase::covers(&covered));
+        let (exact, value) = Evaluator::eval(&fallback, &covered);
+        assert!(exact);
+        assert_eq!(value, Evaluator::eval(&*tablebase, &covered).1);
+
+        let game = Game {
+            my: covered.my | 1 << 0,
+            ..covered
+        };
+        assert!(!TableBase::covers(&game));
+        assert_eq!(
+            Evaluator::eval(&fallback, &game),
+            (false, evaluator.evaluate_with(&game))
+        );
+
+        let agent = Agent::with_evaluator(Rc::new(fallback));
+        let mut node = agent.new_node(covered, 0);
+        assert!(node.is_table());
+        let mut node = agent.new_node(game, 0);
+        assert!(!node.is_table());
+    }
+}
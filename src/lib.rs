@@ -0,0 +1,11 @@
+extern crate onitama_move_gen;
+#[macro_use]
+extern crate serde_derive;
+extern crate tungstenite;
+
+pub mod analysis;
+pub mod connection;
+pub mod evaluator;
+pub mod messages;
+pub mod node;
+pub mod protocol;
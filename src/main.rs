@@ -1,26 +1,26 @@
-use std::{env, mem::take, rc::Rc, time::Instant};
+use std::{env, io, mem::take, rc::Rc, time::Instant};
 
-use connection::{get_msg, get_next_state};
-use messages::StateObj;
-use onitama_move_gen::{gen::Game, tablebase::TableBase};
-use tungstenite::{client::AutoStream, connect, WebSocket};
-
-use crate::{
-    messages::{move_to_command, LitamaMsg, StateMsg},
+use onitama::{
+    connection::{get_msg, get_next_state},
+    evaluator::mate_in,
+    messages::{move_to_command, LitamaMsg, StateMsg, StateObj},
     node::{Agent, Node},
+    protocol,
 };
-
-mod connection;
-mod messages;
-pub mod node;
-
-extern crate onitama_move_gen;
-#[macro_use]
-extern crate serde_derive;
-extern crate tungstenite;
+use onitama_move_gen::{gen::Game, tablebase::TableBase};
+use tungstenite::{client::AutoStream, connect, WebSocket};
 
 fn main() {
-    run_loop();
+    if env::args().nth(1).as_deref() == Some("protocol") {
+        let stdin = io::stdin();
+        protocol::run(
+            |cards| TableBase::new(cards).into(),
+            stdin.lock(),
+            io::stdout(),
+        );
+    } else {
+        run_loop();
+    }
 }
 
 fn run_loop() -> Option<()> {
@@ -113,18 +113,17 @@ impl Runner {
             let res = agent.bns(node);
             println!("* {}", node.get_lower());
             if now2.elapsed().as_millis() > 1000
-                || node.get_lower() == 127
-                || node.get_lower() == -127
+                || mate_in(node.get_lower()).is_some()
                 || res.is_none()
             {
                 break;
             }
         }
-        *node = take(node.get_nodes().iter_mut().next().unwrap());
-
         let game = self.state.game();
-        let cond = |(i, _): &(usize, Game)| node.is_child(*i as u8);
-        let (_, new_game) = game.forward().enumerate().find(cond).unwrap();
+        let child = agent.best_child(node, &game).unwrap();
+        let cond = |n: &&mut Node| n.is_child(child);
+        *node = take(node.get_nodes().iter_mut().find(cond).unwrap());
+        let new_game = game.forward().nth(child as usize).unwrap();
 
         let flip = self.state.current_turn == "red";
         let command = move_to_command(game, new_game, &self.match_id, &self.token, flip);
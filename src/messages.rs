@@ -1,7 +1,6 @@
 use core::panic;
 
-use onitama_move_gen::gen::Game;
-use onitama_move_gen::NAMES;
+use onitama_move_gen::{card::Card, gen::Game, moves::square_name, NAMES};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "messageType")]
@@ -150,14 +149,7 @@ pub struct ErrorMsg {
 
 pub fn translate_pos(pos: usize, flip: bool) -> String {
     let pos = if flip { 24 - pos } else { pos };
-    let row = pos / 5;
-    let col = pos % 5;
-    [
-        "edcba".chars().nth(col).unwrap(),
-        "12345".chars().nth(row).unwrap(),
-    ]
-    .iter()
-    .collect::<String>()
+    square_name(pos as u8)
 }
 
 pub fn move_to_command(
@@ -174,14 +166,24 @@ pub fn move_to_command(
     // token
     command.push_str(token);
     command.push(' ');
-    // card
-    command.push_str(NAMES[new_game.table as usize]);
-    command.push(' ');
-    // from:to
-    let from = game.my & !new_game.other;
-    let to = new_game.other & !game.my;
-    command.push_str(&translate_pos(from.trailing_zeros() as usize, flip));
-    command.push_str(&translate_pos(to.trailing_zeros() as usize, flip));
+    // card from:to
+    command.push_str(&move_to_string(game, new_game, flip));
     println!("{}", command);
     command
 }
+
+/// The move from `game` to `new_game` as `<card> <from><to>` in the notation of `Move`.
+/// Squares are in the frame of blue, so the moves of red are mirrored with `flip`.
+pub fn move_to_string(game: Game, new_game: Game, flip: bool) -> String {
+    let (mv, _) = game
+        .forward_with_moves()
+        .find(|&(_, child)| child == new_game)
+        .expect("new_game is not a child of game");
+    let mv = if flip { mv.mirrored() } else { mv };
+    format!(
+        "{} {}{}",
+        Card(mv.card as u32),
+        square_name(mv.from),
+        square_name(mv.to)
+    )
+}
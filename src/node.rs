@@ -1,22 +1,45 @@
 use std::ptr::{self, NonNull};
-use std::{alloc::Layout, cmp::max, mem::swap, rc::Rc, slice, unreachable};
+use std::{
+    alloc::Layout,
+    cell::{Cell, RefCell},
+    cmp::{max, min},
+    collections::HashMap,
+    mem::swap,
+    rc::Rc,
+    slice, unreachable,
+};
 
 use bumpalo::Bump;
-use onitama_move_gen::{gen::Game, tablebase::TableBase};
+use onitama_move_gen::{
+    gen::{Game, PIECE_MASK},
+    moves::Move,
+    tablebase::TableBase,
+    zobrist::ZobristKeys,
+};
+
+use crate::evaluator::{parent_score, Evaluator, MATE, MATE_BOUND};
 
 #[derive(Clone, Copy, Default)]
 pub struct Leaf {
     table: bool,
-    value: i8,
+    value: i32,
     child: u8,
+    /// Where the last move took a piece of the player to move, in their frame.
+    taken: Option<u8>,
     game: Game,
 }
 
 pub struct Branch<'a> {
-    lower: i8,
-    upper: i8,
+    lower: i32,
+    upper: i32,
     depth: u8,
     child: u8,
+    /// Pieces of both players, which late move reductions compare to find captures.
+    pieces: u8,
+    /// The player who moved to this node could win if it were their turn again.
+    threat: bool,
+    /// See `Leaf::taken`.
+    taken: Option<u8>,
     nodes: &'a mut [Node<'a>],
 }
 
@@ -27,10 +50,13 @@ pub enum Node<'a> {
 
 impl<'a> Node<'a> {
     pub fn is_child(&self, child: u8) -> bool {
-        (match self {
+        self.get_child() == child
+    }
+    pub fn get_child(&self) -> u8 {
+        match self {
             Node::Leaf(leaf) => leaf.child,
             Node::Branch(branch) => branch.child,
-        }) == child
+        }
     }
     pub fn as_branch(&mut self) -> &mut Branch<'a> {
         match self {
@@ -44,7 +70,7 @@ impl<'a> Node<'a> {
     pub fn get_depth(&mut self) -> u8 {
         self.as_branch().depth
     }
-    pub fn get_lower(&mut self) -> i8 {
+    pub fn get_lower(&mut self) -> i32 {
         match self {
             Node::Leaf(leaf) => leaf.value,
             Node::Branch(branch) => branch.lower,
@@ -62,6 +88,34 @@ impl<'a> Node<'a> {
             Node::Branch(_) => unreachable!(),
         }
     }
+    /// Pieces of both players.
+    pub fn total_pieces(&self) -> u8 {
+        match self {
+            Node::Leaf(leaf) => total_pieces(&leaf.game),
+            Node::Branch(branch) => branch.pieces,
+        }
+    }
+    /// See `Leaf::taken`.
+    pub fn taken(&self) -> Option<u8> {
+        match self {
+            Node::Leaf(leaf) => leaf.taken,
+            Node::Branch(branch) => branch.taken,
+        }
+    }
+    /// The player to move wins with their next move, so the move to this node loses.
+    pub fn wins_at_once(&self) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.table && leaf.value == MATE - 1,
+            Node::Branch(_) => false,
+        }
+    }
+    /// Whether the move to this node threatens to win, see `Branch::threat`.
+    pub fn is_threat(&self) -> bool {
+        match self {
+            Node::Leaf(leaf) => is_threat(&leaf.game),
+            Node::Branch(branch) => branch.threat,
+        }
+    }
 }
 
 impl Default for Node<'_> {
@@ -70,19 +124,213 @@ impl Default for Node<'_> {
     }
 }
 
+pub const MAX_EXTENSIONS: u8 = 4;
+
+/// Children searched at full depth before late move reductions start.
+pub const LMR_MOVES: usize = 3;
+
+/// Half width of the window around the previous score in `Agent::aspiration_search`.
+pub const ASPIRATION_WINDOW: i32 = 10;
+
+#[inline]
+fn total_pieces(game: &Game) -> u8 {
+    (game.count_pieces() + game.count_other_pieces()) as u8
+}
+
+#[inline]
+fn is_threat(game: &Game) -> bool {
+    !game.is_loss() && game.null_move().is_win()
+}
+
+/// The square where the move from `game` to `new_game` took a piece, in the frame of `new_game`.
+#[inline]
+fn taken_square(game: &Game, new_game: &Game) -> Option<u8> {
+    let taken = game.other & !new_game.my & PIECE_MASK;
+    if taken == 0 {
+        None
+    } else {
+        Some(taken.trailing_zeros() as u8)
+    }
+}
+
+/// Depth and extensions left for the children of `node` searched at `depth`.
+/// The children are searched one ply deeper, at most `extensions` times on a line,
+/// if there is a single reply, or if the last move took a piece and every reply
+/// that does not lose at once takes back on that square.
+#[inline]
+fn child_depth(node: &Branch, depth: u8, extensions: u8) -> (u8, u8) {
+    if extensions > 0 && (node.nodes.len() == 1 || is_forced_recapture(node)) {
+        (depth, extensions - 1)
+    } else {
+        (depth - 1, extensions)
+    }
+}
+
+#[inline]
+fn is_forced_recapture(node: &Branch) -> bool {
+    let square = match node.taken {
+        Some(square) => 24 - square,
+        None => return false,
+    };
+    let mut replies = node
+        .nodes
+        .iter()
+        .filter(|new_node| !new_node.wins_at_once())
+        .peekable();
+    replies.peek().is_some() && replies.all(|new_node| new_node.taken() == Some(square))
+}
+
+/// Beta of a child for the null window `(beta - 1, beta)` of its parent,
+/// so `parent_score(eval) >= beta` exactly when the child scores `eval < null_beta(beta)`.
+#[inline(always)]
+fn null_beta(beta: i32) -> i32 {
+    let beta = if beta > MATE_BOUND {
+        beta + 1
+    } else if beta <= -MATE_BOUND {
+        beta - 1
+    } else {
+        beta
+    };
+    1 - beta
+}
+
+/// Window of a child for the window `(alpha, beta)` of its parent.
+#[inline(always)]
+fn child_window(alpha: i32, beta: i32) -> (i32, i32) {
+    (null_beta(beta) - 1, null_beta(alpha + 1))
+}
+
+/// Search settings for positions with little material left, see `Agent::set_endgame`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Endgame {
+    /// Positions with at most this many pieces of both players together are endgames.
+    pub pieces: usize,
+    /// Plies added to the depth of a search that starts in an endgame.
+    pub extra_depth: u8,
+}
+
+/// Evaluations by zobrist hash, see `Agent::set_eval_cache`.
+struct EvalCache {
+    keys: ZobristKeys,
+    values: HashMap<u64, (bool, i32)>,
+    capacity: usize,
+}
+
 pub struct Agent {
-    tablebase: Rc<TableBase>,
+    evaluator: Rc<dyn Evaluator>,
     bump: Bump,
+    pvs: bool,
+    lmr: bool,
+    endgame: Option<Endgame>,
+    eval_cache: RefCell<Option<EvalCache>>,
+    visited: Cell<u64>,
 }
 
 impl Agent {
     pub fn new(tablebase: Rc<TableBase>) -> Self {
+        Self::with_evaluator(tablebase)
+    }
+
+    pub fn with_evaluator(evaluator: Rc<dyn Evaluator>) -> Self {
         Self {
-            tablebase,
+            evaluator,
             bump: Bump::new(),
+            pvs: true,
+            lmr: false,
+            endgame: None,
+            eval_cache: RefCell::new(None),
+            visited: Cell::new(0),
         }
     }
 
+    /// The settings that play best with `protocol::strong_move`: principal variation search,
+    /// late move reductions and deeper searches in endgames of four pieces.
+    /// The evaluation cache stays off, since the evaluators of this crate are cheap.
+    pub fn strong(evaluator: Rc<dyn Evaluator>) -> Self {
+        let mut agent = Self::with_evaluator(evaluator);
+        agent.set_pvs(true);
+        agent.set_lmr(true);
+        agent.set_endgame(Some(Endgame {
+            pieces: 4,
+            extra_depth: 2,
+        }));
+        agent
+    }
+
+    /// Turns principal variation search in `search` on or off, off searches every child
+    /// with the full window like plain alpha-beta.
+    pub fn set_pvs(&mut self, pvs: bool) {
+        self.pvs = pvs;
+    }
+
+    /// Turns late move reductions in `search` on or off, off by default.
+    /// In null window searches of principal variation search the children after
+    /// the first `LMR_MOVES` are searched one ply shallower first,
+    /// and again at full depth if they beat alpha.
+    /// Captures and moves that threaten to win are never reduced.
+    pub fn set_lmr(&mut self, lmr: bool) {
+        self.lmr = lmr;
+    }
+
+    /// With few pieces left there is nothing for quiescence to settle apart from the captures
+    /// of the last pieces, and the game is decided by reaching the temple, which takes depth.
+    /// So endgames skip quiescence and searches that start in one go deeper, see `root_depth`.
+    /// Off by default.
+    pub fn set_endgame(&mut self, endgame: Option<Endgame>) {
+        self.endgame = endgame;
+    }
+
+    #[inline]
+    pub fn is_endgame(&self, game: &Game) -> bool {
+        match self.endgame {
+            Some(endgame) => game.count_pieces() + game.count_other_pieces() <= endgame.pieces,
+            None => false,
+        }
+    }
+
+    /// Depth to search `game` to for a requested `depth`, deeper in endgames.
+    pub fn root_depth(&self, game: &Game, depth: u8) -> u8 {
+        match self.endgame {
+            Some(endgame) if self.is_endgame(game) => depth.saturating_add(endgame.extra_depth),
+            _ => depth,
+        }
+    }
+
+    /// Keeps up to `capacity` evaluations, so positions that are reached again by another
+    /// order of moves are not evaluated again. Worth it for evaluators that are slow.
+    /// The cache is cleared when it is full. `None` turns it off, which is the default.
+    pub fn set_eval_cache(&mut self, capacity: Option<usize>) {
+        *self.eval_cache.get_mut() = capacity.map(|capacity| EvalCache {
+            keys: ZobristKeys::new(0),
+            values: HashMap::with_capacity(capacity),
+            capacity,
+        });
+    }
+
+    #[inline]
+    fn evaluate(&self, game: &Game) -> (bool, i32) {
+        let mut cache = self.eval_cache.borrow_mut();
+        let cache = match cache.as_mut() {
+            Some(cache) => cache,
+            None => return self.evaluator.eval(game),
+        };
+        let hash = game.zobrist_hash(&cache.keys);
+        if let Some(&value) = cache.values.get(&hash) {
+            return value;
+        }
+        let value = self.evaluator.eval(game);
+        if cache.values.len() >= cache.capacity {
+            cache.values.clear();
+        }
+        cache.values.insert(hash, value);
+        value
+    }
+
+    /// Number of nodes `search` was called on.
+    pub fn visited(&self) -> u64 {
+        self.visited.get()
+    }
+
     pub fn copy<'a>(&'a self, node: &Node) -> Node<'a> {
         match node {
             Node::Leaf(leaf) => Node::Leaf(*leaf),
@@ -94,6 +342,9 @@ impl Agent {
                 upper: branch.upper,
                 depth: branch.depth,
                 child: branch.child,
+                pieces: branch.pieces,
+                threat: branch.threat,
+                taken: branch.taken,
             }),
         }
     }
@@ -101,15 +352,19 @@ impl Agent {
     #[inline(always)]
     pub fn new_node(&self, game: Game, child: u8) -> Node {
         let (table, value) = if game.is_loss() {
-            (true, -127)
+            (true, -MATE)
+        } else if game.is_win() {
+            // the winning move is enough, so the children are never generated
+            (true, MATE - 1)
         } else {
-            self.tablebase.eval(game)
+            self.evaluate(&game)
         };
         Node::Leaf(Leaf {
             game,
             table,
             value,
             child,
+            taken: None,
         })
     }
 
@@ -119,7 +374,13 @@ impl Agent {
                 .game
                 .forward()
                 .enumerate()
-                .map(|(new_child, new_game)| self.new_node(new_game, new_child as u8));
+                .map(|(new_child, new_game)| {
+                    let mut new_node = self.new_node(new_game, new_child as u8);
+                    if let Node::Leaf(new_leaf) = &mut new_node {
+                        new_leaf.taken = taken_square(&leaf.game, &new_game);
+                    }
+                    new_node
+                });
 
             let layout = Layout::array::<Node>(iter.len()).unwrap();
             let dst = self.bump.try_alloc_layout(layout).ok()?.cast::<Node>();
@@ -134,9 +395,12 @@ impl Agent {
             };
             *node = Node::Branch(Branch {
                 lower: leaf.value,
-                upper: 127,
+                upper: MATE,
                 depth: 0,
                 child: leaf.child,
+                pieces: total_pieces(&leaf.game),
+                threat: is_threat(&leaf.game),
+                taken: leaf.taken,
                 nodes,
             });
         };
@@ -147,10 +411,10 @@ impl Agent {
         self.expand(node)?;
         let depth = node.as_branch().depth + 1;
         let mut guess = node.as_branch().lower;
-        println!("depth: {}", depth);
+        eprintln!("depth: {}", depth);
         while node.as_branch().depth != depth || node.as_branch().lower != node.as_branch().upper {
-            let beta = max(node.as_branch().lower.saturating_add(1), guess);
-            guess = self.alpha_beta(node, beta, depth)?;
+            let beta = max(node.as_branch().lower + 1, guess);
+            guess = self.alpha_beta(node, beta, depth, MAX_EXTENSIONS)?;
         }
         assert!(node.as_branch().depth == depth);
         Some(())
@@ -201,7 +465,43 @@ impl Agent {
         // }
     }
 
-    pub fn alpha_beta<'a>(&'a self, node: &mut Node<'a>, beta: i8, depth: u8) -> Option<i8> {
+    /// Index of the child of `game` to play after `bns`, the same as in `Game::forward`.
+    /// Children with the best score are decided by the smallest `Move::to_u16`,
+    /// so the choice does not depend on the order the search left them in.
+    /// If the last iteration did not finish the first child is played.
+    pub fn best_child<'a>(&'a self, node: &mut Node<'a>, game: &Game) -> Option<u8> {
+        let branch = node.as_branch();
+        let first = branch.nodes.first()?.get_child();
+        if branch.lower != branch.upper || branch.depth == 0 {
+            return Some(first);
+        }
+        let (value, depth) = (branch.lower, branch.depth);
+        let beta = null_beta(value);
+        let moves = game.legal_moves_small();
+        let mut best = (moves[first as usize].to_u16(), first);
+        for new_node in branch.nodes.iter_mut() {
+            let child = new_node.get_child();
+            let key = moves[child as usize].to_u16();
+            if key < best.0 {
+                match self.alpha_beta(new_node, beta, depth - 1, MAX_EXTENSIONS) {
+                    Some(eval) if parent_score(eval) >= value => best = (key, child),
+                    Some(_) => {}
+                    None => return Some(first),
+                }
+            }
+        }
+        Some(best.1)
+    }
+
+    /// Nodes with a single reply or a forced recapture are searched one ply deeper,
+    /// at most `extensions` times on a line, see `child_depth`.
+    pub fn alpha_beta<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        beta: i32,
+        depth: u8,
+        extensions: u8,
+    ) -> Option<i32> {
         if depth == 0 {
             return self.quiescence(node, beta);
         }
@@ -218,19 +518,22 @@ impl Agent {
                 return Some(node.upper);
             }
         } else {
-            node.lower = -127;
-            node.upper = 127;
+            node.lower = -MATE;
+            node.upper = MATE;
             node.depth = depth;
         }
+        let (new_depth, extensions) = child_depth(node, depth, extensions);
         let (first, rest) = node.nodes.split_first_mut().unwrap();
-        let mut guess = -self.alpha_beta(first, -beta + 1, depth - 1)?;
+        let mut guess =
+            parent_score(self.alpha_beta(first, null_beta(beta), new_depth, extensions)?);
         if guess >= beta {
             node.lower = guess;
             debug_assert!(node.lower <= node.upper);
             return Some(guess);
         }
         for new_node in rest {
-            let eval = -self.alpha_beta(new_node, -beta + 1, depth - 1)?;
+            let eval =
+                parent_score(self.alpha_beta(new_node, null_beta(beta), new_depth, extensions)?);
             guess = max(guess, eval);
             if eval >= beta {
                 swap(first, new_node);
@@ -244,7 +547,183 @@ impl Agent {
         Some(guess)
     }
 
-    pub fn quiescence<'a>(&'a self, node: &mut Node<'a>, beta: i8) -> Option<i8> {
+    /// Fail-soft alpha-beta with the window `(alpha, beta)` and the same extensions as `alpha_beta`.
+    /// The bounds found are stored in the nodes, so both searches can share a tree.
+    /// With principal variation search only the first child gets the full window,
+    /// the others are searched with a null window and again if they land inside the window.
+    pub fn search<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        mut alpha: i32,
+        beta: i32,
+        depth: u8,
+        extensions: u8,
+    ) -> Option<i32> {
+        self.visited.set(self.visited.get() + 1);
+        if depth == 0 {
+            return self.quiescence(node, beta);
+        }
+        if node.is_table() {
+            return Some(node.get_lower());
+        }
+        self.expand(node)?;
+        let node = node.as_branch();
+        if node.depth == depth {
+            if node.lower >= beta || node.lower == node.upper {
+                return Some(node.lower);
+            }
+            if node.upper <= alpha {
+                return Some(node.upper);
+            }
+        } else {
+            node.lower = -MATE;
+            node.upper = MATE;
+            node.depth = depth;
+        }
+        let (new_depth, extensions) = child_depth(node, depth, extensions);
+        let old_alpha = alpha;
+        let mut guess = -MATE;
+        for i in 0..node.nodes.len() {
+            let new_node = &mut node.nodes[i];
+            let eval = if i == 0 || !self.pvs {
+                let (alpha, beta) = child_window(alpha, beta);
+                parent_score(self.search(new_node, alpha, beta, new_depth, extensions)?)
+            } else {
+                let (scout_alpha, scout_beta) = child_window(alpha, alpha + 1);
+                let reduce = self.lmr
+                    && beta - old_alpha == 1
+                    && i >= LMR_MOVES
+                    && new_depth >= 2
+                    && new_node.total_pieces() == node.pieces
+                    && !new_node.is_threat();
+                let mut eval = MATE;
+                if reduce {
+                    let depth = new_depth - 1;
+                    eval = parent_score(self.search(
+                        new_node,
+                        scout_alpha,
+                        scout_beta,
+                        depth,
+                        extensions,
+                    )?);
+                }
+                if eval > alpha {
+                    let depth = new_depth;
+                    eval = parent_score(self.search(
+                        new_node,
+                        scout_alpha,
+                        scout_beta,
+                        depth,
+                        extensions,
+                    )?);
+                }
+                if eval > alpha && eval < beta {
+                    let (alpha, beta) = child_window(eval, beta);
+                    parent_score(self.search(new_node, alpha, beta, new_depth, extensions)?)
+                } else {
+                    eval
+                }
+            };
+            if eval > guess {
+                guess = eval;
+                node.nodes.swap(0, i);
+            }
+            alpha = max(alpha, eval);
+            if alpha >= beta {
+                break;
+            }
+        }
+        if guess < beta {
+            node.upper = min(node.upper, guess);
+        }
+        if guess > old_alpha {
+            node.lower = max(node.lower, guess);
+        }
+        debug_assert!(node.lower <= node.upper);
+        Some(guess)
+    }
+
+    /// `search` with a window of `ASPIRATION_WINDOW` around `guess`, the score of the
+    /// previous iteration. If the score falls outside it is searched again with the full window.
+    /// The tree keeps the bounds of the first search, so the second one is cheaper.
+    pub fn aspiration_search<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        guess: i32,
+        depth: u8,
+    ) -> Option<i32> {
+        let alpha = (guess - ASPIRATION_WINDOW).max(-MATE);
+        let beta = (guess + ASPIRATION_WINDOW).min(MATE);
+        let eval = self.search(node, alpha, beta, depth, MAX_EXTENSIONS)?;
+        if eval > alpha && eval < beta {
+            Some(eval)
+        } else {
+            self.search(node, -MATE, MATE, depth, MAX_EXTENSIONS)
+        }
+    }
+
+    /// Fail-soft alpha-beta like `search` without principal variation search,
+    /// which writes the best line below `node` into `line` as it returns.
+    /// `game` is the game of `node`, which is needed to turn children into moves.
+    /// The captures searched by quiescence at depth 0 are not part of the line.
+    /// Bounds are not stored, so the tree can not be searched again.
+    #[allow(clippy::too_many_arguments)]
+    pub fn search_line<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        game: &Game,
+        mut alpha: i32,
+        beta: i32,
+        depth: u8,
+        extensions: u8,
+        line: &mut Vec<Move>,
+    ) -> Option<i32> {
+        line.clear();
+        if !game.is_loss() && game.is_win() {
+            // wins are not expanded, but the line ends with the winning move
+            line.extend(game.winning_moves().first());
+        }
+        if depth == 0 {
+            return self.quiescence(node, beta);
+        }
+        if node.is_table() {
+            return Some(node.get_lower());
+        }
+        self.expand(node)?;
+        let node = node.as_branch();
+        let (new_depth, extensions) = child_depth(node, depth, extensions);
+        let moves = game.legal_moves_small();
+        let mut new_line = Vec::new();
+        let mut guess = -MATE;
+        for (i, new_node) in node.nodes.iter_mut().enumerate() {
+            let mv = moves[new_node.get_child() as usize];
+            let new_game = game.apply(mv);
+            let (new_alpha, new_beta) = child_window(alpha, beta);
+            let eval = parent_score(self.search_line(
+                new_node,
+                &new_game,
+                new_alpha,
+                new_beta,
+                new_depth,
+                extensions,
+                &mut new_line,
+            )?);
+            // a lost position still has a line
+            if eval > guess || i == 0 {
+                guess = eval;
+                line.clear();
+                line.push(mv);
+                line.extend_from_slice(&new_line);
+            }
+            alpha = max(alpha, eval);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(guess)
+    }
+
+    pub fn quiescence<'a>(&'a self, node: &mut Node<'a>, beta: i32) -> Option<i32> {
         match node {
             Node::Branch(branch) => {
                 debug_assert_eq!(branch.depth, 0);
@@ -252,7 +731,7 @@ impl Agent {
                 return Some(branch.lower);
             }
             Node::Leaf(leaf) => {
-                if leaf.table {
+                if leaf.table || self.is_endgame(&leaf.game) {
                     return Some(leaf.value);
                 }
             }
@@ -262,12 +741,12 @@ impl Agent {
         let node = node.as_branch();
         let (first, rest) = node.nodes.split_first_mut().unwrap();
         if first.piece_count() < pieces {
-            let eval = -self.quiescence(first, -beta + 1)?;
+            let eval = parent_score(self.quiescence(first, null_beta(beta))?);
             node.lower = max(node.lower, eval);
         }
         for new_node in rest {
             if new_node.piece_count() < pieces {
-                let eval = -self.quiescence(new_node, -beta + 1)?;
+                let eval = parent_score(self.quiescence(new_node, null_beta(beta))?);
                 node.lower = max(node.lower, eval);
                 swap(first, new_node);
             }
@@ -277,23 +756,438 @@ impl Agent {
     }
 }
 
+/// Whether `game` scores at least `beta` at `depth`, for building scout searches like MTD(f).
+/// This is a single null window `alpha_beta` on a new tree. The search is fail-soft,
+/// so it returns a lower bound on the score that is at least `beta` when it succeeds
+/// and an upper bound below `beta` when it fails, of which only the comparison is kept.
+pub fn null_window(game: Game, depth: u8, beta: i32, evaluator: Rc<dyn Evaluator>) -> bool {
+    let agent = Agent::with_evaluator(evaluator);
+    let mut node = agent.new_node(game, 0);
+    let eval = agent
+        .alpha_beta(&mut node, beta, depth, MAX_EXTENSIONS)
+        .expect("out of memory");
+    eval >= beta
+}
+
+/// MTD(f), which repeats null window searches from `first_guess` until the bounds meet.
+/// All searches share one tree, which keeps the bounds of earlier searches
+/// like a transposition table, so each search only looks at what changed.
+pub fn mtdf(game: Game, depth: u8, first_guess: i32, evaluator: Rc<dyn Evaluator>) -> i32 {
+    let agent = Agent::with_evaluator(evaluator);
+    let mut node = agent.new_node(game, 0);
+    let (mut lower, mut upper) = (-MATE, MATE);
+    let mut guess = first_guess;
+    while lower < upper {
+        let beta = max(guess, lower + 1);
+        guess = agent
+            .alpha_beta(&mut node, beta, depth, MAX_EXTENSIONS)
+            .expect("out of memory");
+        if guess < beta {
+            upper = guess;
+        } else {
+            lower = guess;
+        }
+    }
+    guess
+}
+
+/// The best line from `game` with a full window search of `depth` plies.
+/// The line is passed up through the recursion instead of being read from the tree afterwards,
+/// so it does not depend on which children the tree kept in front.
+pub fn best_line(game: Game, depth: u8, evaluator: Rc<dyn Evaluator>) -> Vec<Move> {
+    let agent = Agent::with_evaluator(evaluator);
+    let mut node = agent.new_node(game, 0);
+    let mut line = Vec::new();
+    agent
+        .search_line(
+            &mut node,
+            &game,
+            -MATE,
+            MATE,
+            depth,
+            MAX_EXTENSIONS,
+            &mut line,
+        )
+        .expect("out of memory");
+    line
+}
+
 #[cfg(test)]
 mod test {
-    use std::mem::size_of;
+    use std::{cell::Cell, mem::size_of, rc::Rc};
 
     use bumpalo::Bump;
-    use onitama_move_gen::{eval::Eval, gen::Game, tablebase::TableBase};
+    use onitama_move_gen::{
+        eval::Eval, gen::Game, moves::Move, perft::TEST_GAME, tablebase::TableBase,
+    };
 
-    use crate::node::{Branch, Leaf, Node};
+    use crate::{
+        evaluator::{mate_in, Evaluator, HandcraftedEvaluator, MATE, MATE_BOUND},
+        node::{best_line, mtdf, null_window, Agent, Branch, Leaf, Node, MAX_EXTENSIONS},
+    };
 
     #[test]
     fn try_forward_tie() {
         dbg!(Eval::new_tie().forward());
     }
 
+    #[test]
+    fn single_reply_extension() {
+        let agent = Agent::new(TableBase::empty().into());
+        // the king on 0 can only move to 5
+        let game = Game {
+            my: 1,
+            other: 1 | 1 << 2 | 1 << 4,
+            cards: (1 << 0 | 1 << 11) | (1 << 14 | 1 << 15) << 16,
+            table: 13,
+        };
+        assert_eq!(game.count_moves(), 1);
+
+        let mut node = agent.new_node(game, 0);
+        agent.alpha_beta(&mut node, 0, 1, MAX_EXTENSIONS);
+        assert_eq!(node.get_nodes()[0].get_depth(), 1);
+
+        let mut node = agent.new_node(game, 0);
+        agent.alpha_beta(&mut node, 0, 1, 0);
+        assert_eq!(node.get_nodes()[0].get_depth(), 0);
+
+        let mut node = agent.new_node(TEST_GAME, 0);
+        agent.alpha_beta(&mut node, 0, 1, MAX_EXTENSIONS);
+        assert_eq!(node.get_nodes()[0].get_depth(), 0);
+    }
+
+    #[test]
+    fn recapture_extension() {
+        let agent = Agent::with_evaluator(Rc::new(HandcraftedEvaluator::default()));
+        // crab takes the pawn on 18 next to the king on 24, which has to take back
+        // or lose to the same pawn, and the king on 9 has quiet moves
+        let game = Game {
+            my: 1 << 9 | 1 << 16 | 9 << 25,
+            other: 1 | 1 << 6,
+            cards: (1 << 0 | 1 << 4) | (1 << 3 | 1 << 5) << 16,
+            table: 9,
+        };
+        let capture = Move {
+            from: 16,
+            to: 18,
+            card: 4,
+        };
+        let is_capture = |new_node: &Node| {
+            game.forward().nth(new_node.get_child() as usize) == Some(game.apply(capture))
+        };
+        let searched_depth = |node: &Node| match node {
+            Node::Leaf(_) => 0,
+            Node::Branch(branch) => branch.depth,
+        };
+
+        let mut node = agent.new_node(game, 0);
+        agent.search(&mut node, -MATE, MATE, 2, MAX_EXTENSIONS);
+        let mut quiet = 0;
+        for new_node in node.get_nodes().iter_mut() {
+            if is_capture(new_node) {
+                // the recapture is searched at depth 1 instead of 0
+                let replies = new_node.get_nodes();
+                let mut recaptures = replies.iter().filter(|reply| !reply.wins_at_once());
+                let recapture = recaptures.next().unwrap();
+                assert!(recaptures.next().is_none());
+                assert_eq!(recapture.taken(), Some(18));
+                assert_eq!(searched_depth(recapture), 1);
+            } else if new_node.taken().is_none() && !new_node.wins_at_once() {
+                quiet += 1;
+                assert!(new_node
+                    .get_nodes()
+                    .iter()
+                    .all(|reply| searched_depth(reply) == 0));
+            }
+        }
+        assert!(quiet > 0);
+
+        let mut node = agent.new_node(game, 0);
+        agent.search(&mut node, -MATE, MATE, 2, 0);
+        for new_node in node.get_nodes().iter_mut() {
+            if is_capture(new_node) {
+                assert!(new_node
+                    .get_nodes()
+                    .iter()
+                    .all(|reply| searched_depth(reply) == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn pvs_matches_alpha_beta() {
+        let mut visited = [0; 2];
+        for i in 0..10 {
+            // fixed lines from the start position
+            let mut game = TEST_GAME;
+            for ply in 0..i {
+                let children: Vec<Game> = game.forward().collect();
+                game = children[(i * 7 + ply * 3) % children.len()];
+            }
+            assert!(!game.is_loss());
+
+            let mut scores = Vec::new();
+            for (i, &pvs) in [true, false].iter().enumerate() {
+                let mut agent = Agent::new(TableBase::empty().into());
+                agent.set_pvs(pvs);
+                let mut node = agent.new_node(game, 0);
+                let mut score = Vec::new();
+                for depth in 1..=4 {
+                    score.push(agent.search(&mut node, -127, 127, depth, MAX_EXTENSIONS));
+                }
+                visited[i] += agent.visited();
+                scores.push(score);
+            }
+            assert_eq!(scores[0], scores[1]);
+
+            // the null window searches of bns agree as well
+            let agent = Agent::new(TableBase::empty().into());
+            let mut node = agent.new_node(game, 0);
+            for depth in 1..=4 {
+                agent.bns(&mut node);
+                assert_eq!(Some(node.get_lower()), scores[0][depth - 1]);
+            }
+        }
+        assert!(visited[0] < visited[1]);
+    }
+
+    #[test]
+    fn null_window_matches_search() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        let mut game = TEST_GAME;
+        for ply in 0..6 {
+            let children: Vec<Game> = game.forward().collect();
+            game = children[ply * 5 % children.len()];
+            for depth in 1..=3 {
+                let agent = Agent::with_evaluator(Rc::clone(&evaluator));
+                let mut node = agent.new_node(game, 0);
+                let score = agent
+                    .search(&mut node, -127, 127, depth, MAX_EXTENSIONS)
+                    .unwrap();
+                for &beta in &[
+                    -126,
+                    -40,
+                    score - 1,
+                    score,
+                    score.saturating_add(1),
+                    40,
+                    127,
+                ] {
+                    let beta = beta.max(-126);
+                    assert_eq!(
+                        null_window(game, depth, beta, Rc::clone(&evaluator)),
+                        score >= beta
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mtdf_matches_search() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        let mut game = TEST_GAME;
+        for ply in 0..6 {
+            let children: Vec<Game> = game.forward().collect();
+            game = children[ply * 3 % children.len()];
+            for depth in 1..=3 {
+                let agent = Agent::with_evaluator(Rc::clone(&evaluator));
+                let mut node = agent.new_node(game, 0);
+                let score = agent
+                    .search(&mut node, -127, 127, depth, MAX_EXTENSIONS)
+                    .unwrap();
+                for &first_guess in &[0, -50, 100, score] {
+                    assert_eq!(mtdf(game, depth, first_guess, Rc::clone(&evaluator)), score);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn best_line_matches_tree() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        let mut game = TEST_GAME;
+        for ply in 0..6 {
+            let children: Vec<Game> = game.forward().collect();
+            game = children[ply * 7 % children.len()];
+            for depth in 1..=4 {
+                let line = best_line(game, depth, Rc::clone(&evaluator));
+                assert!(!line.is_empty());
+
+                // the best child is kept in front by `search`, so the tree has the same line
+                let mut agent = Agent::with_evaluator(Rc::clone(&evaluator));
+                agent.set_pvs(false);
+                let mut node = agent.new_node(game, 0);
+                agent.search(&mut node, -127, 127, depth, MAX_EXTENSIONS);
+                let mut tree_line = Vec::new();
+                let (mut node, mut tree_game) = (&mut node, game);
+                while let Node::Branch(branch) = node {
+                    if branch.depth == 0 {
+                        break;
+                    }
+                    let first = &mut branch.nodes[0];
+                    let mv = tree_game.legal_moves_small()[first.get_child() as usize];
+                    tree_line.push(mv);
+                    tree_game = tree_game.apply(mv);
+                    node = first;
+                }
+                if !tree_game.is_loss() && tree_game.is_win() {
+                    tree_line.push(tree_game.winning_moves()[0]);
+                }
+                assert_eq!(line, tree_line);
+            }
+        }
+    }
+
+    #[test]
+    fn forced_mate_without_overflow() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        // a win in 5 plies but not in 3, from a random game
+        let game = Game {
+            my: 67117319,
+            other: 167772219,
+            cards: 35653888,
+            table: 14,
+        };
+        let agent = Agent::with_evaluator(Rc::clone(&evaluator));
+        let mut node = agent.new_node(game, 0);
+        let scores: Vec<i32> = (1..=5)
+            .map(|depth| {
+                agent
+                    .search(&mut node, -MATE, MATE, depth, MAX_EXTENSIONS)
+                    .unwrap()
+            })
+            .collect();
+        assert!(
+            scores[..3].iter().all(|&score| mate_in(score).is_none()),
+            "{:?}",
+            scores
+        );
+        assert!(
+            scores[3..].iter().all(|&score| score == MATE - 5),
+            "{:?}",
+            scores
+        );
+        assert_eq!(mate_in(scores[4]), Some(5));
+        assert_eq!(mtdf(game, 4, 0, Rc::clone(&evaluator)), MATE - 5);
+
+        // the lowest null window has to negate `-MATE`
+        for depth in 1..=4 {
+            assert!(null_window(game, depth, -MATE, Rc::clone(&evaluator)));
+            assert_eq!(
+                null_window(game, depth, MATE - 5, Rc::clone(&evaluator)),
+                depth >= 4
+            );
+            assert!(!null_window(game, depth, MATE - 4, Rc::clone(&evaluator)));
+        }
+        let line = best_line(game, 4, Rc::clone(&evaluator));
+        assert_eq!(line.len(), 5);
+        let end = line.iter().fold(game, |game, &mv| game.apply(mv));
+        assert!(end.is_loss());
+
+        // two plies further along the line the win is closer and scores higher
+        let closer = line[..2].iter().fold(game, |game, &mv| game.apply(mv));
+        let mut node = agent.new_node(closer, 0);
+        let score = agent
+            .search(&mut node, -MATE, MATE, 4, MAX_EXTENSIONS)
+            .unwrap();
+        assert_eq!(mate_in(score), Some(3));
+        assert!(score > scores[4]);
+    }
+
+    #[test]
+    fn lmr_finds_tactics() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        // positions from random games with a win found at depth 4
+        let tactics = [
+            (134222360, 167772328, 67403840, 1),
+            (67121156, 83886852, 270533136, 2),
+            (67113494, 167772401, 1212928, 8),
+            (67113052, 335545371, 268584960, 9),
+        ];
+        let mut total = [0; 2];
+        for &(my, other, cards, table) in &tactics {
+            let game = Game {
+                my,
+                other,
+                cards,
+                table,
+            };
+            let mut depths = Vec::new();
+            for (i, &lmr) in [false, true].iter().enumerate() {
+                let mut agent = Agent::with_evaluator(Rc::clone(&evaluator));
+                agent.set_lmr(lmr);
+                let mut node = agent.new_node(game, 0);
+                let depth = (1..=4)
+                    .find(|&depth| {
+                        let score = agent.search(&mut node, -MATE, MATE, depth, MAX_EXTENSIONS);
+                        score.unwrap() > MATE_BOUND
+                    })
+                    .unwrap();
+                depths.push(depth);
+                total[i] += agent.visited();
+            }
+            assert_eq!(depths[0], depths[1]);
+        }
+        assert!(total[1] < total[0], "{:?}", total);
+    }
+
+    struct CountingEvaluator {
+        evaluator: HandcraftedEvaluator,
+        calls: Cell<u64>,
+    }
+
+    impl Evaluator for CountingEvaluator {
+        fn eval(&self, game: &Game) -> (bool, i32) {
+            self.calls.set(self.calls.get() + 1);
+            self.evaluator.eval(game)
+        }
+    }
+
+    #[test]
+    fn eval_cache() {
+        let mut results = Vec::new();
+        for &capacity in &[None, Some(1 << 16), Some(100)] {
+            let evaluator = Rc::new(CountingEvaluator {
+                evaluator: HandcraftedEvaluator::default(),
+                calls: Cell::new(0),
+            });
+            let mut agent = Agent::with_evaluator(evaluator.clone());
+            agent.set_eval_cache(capacity);
+            let mut node = agent.new_node(TEST_GAME, 0);
+            let eval = agent.search(&mut node, -MATE, MATE, 4, MAX_EXTENSIONS);
+            results.push((eval, evaluator.calls.get()));
+        }
+        // the same score with fewer evaluations, also when the cache is cleared on the way
+        assert_eq!(results[1].0, results[0].0);
+        assert_eq!(results[2].0, results[0].0);
+        assert!(results[1].1 < results[0].1);
+        assert!(results[2].1 <= results[0].1);
+    }
+
+    #[test]
+    fn win_is_exact() {
+        let agent = Agent::new(TableBase::empty().into());
+        // my king on 17 steps onto the temple
+        let game = Game {
+            my: 1 << 17 | 17 << 25,
+            ..TEST_GAME
+        };
+        assert!(game.is_win());
+        let mut node = agent.new_node(game, 0);
+        assert!(node.is_table());
+        assert_eq!(node.get_lower(), MATE - 1);
+        // the node is not expanded by the search
+        assert_eq!(
+            agent.search(&mut node, -MATE, MATE, 3, MAX_EXTENSIONS),
+            Some(MATE - 1)
+        );
+        assert!(matches!(node, Node::Leaf(_)));
+    }
+
     #[test]
     fn try_bump() {
-        assert_eq!(size_of::<Branch>(), 24);
+        assert_eq!(size_of::<Branch>(), 32);
         // let bump = Bump::new();
         // let my = bump.alloc_with(|| {
         //     let nodes = bump.alloc_slice_fill_default(1);
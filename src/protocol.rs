@@ -0,0 +1,389 @@
+use std::{
+    cmp::min,
+    io::{BufRead, Write},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use onitama_move_gen::{gen::Game, tablebase::TableBase};
+
+use crate::{
+    evaluator::{mate_in, Evaluator},
+    messages::{move_to_string, CardsObj, IndicesObj, StateObj},
+    node::Agent,
+};
+
+// Line based protocol:
+// position <board> <red|blue> <red card>,<red card> <blue card>,<blue card> <side card>
+// go depth <n> | go movetime <ms> | go time <ms> inc <ms> [movestogo <n>]
+// quit
+// The board uses the litama format and moves are printed like `bestmove <card> <from><to>`.
+
+pub enum Limit {
+    Depth(u8),
+    MoveTime(u128),
+}
+
+pub fn parse_position(args: &[&str]) -> Option<StateObj> {
+    match args {
+        [board, turn, red, blue, side] => {
+            let mut red = red.split(',').map(str::to_string);
+            let mut blue = blue.split(',').map(str::to_string);
+            Some(StateObj {
+                current_turn: turn.to_string(),
+                cards: CardsObj {
+                    red: [red.next()?, red.next()?],
+                    blue: [blue.next()?, blue.next()?],
+                    side: side.to_string(),
+                },
+                indices: IndicesObj { red: 0, blue: 0 },
+                board: board.to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+pub fn parse_go(args: &[&str]) -> Option<Limit> {
+    let millis = |ms: &str| ms.parse().ok().map(Duration::from_millis);
+    match args {
+        ["depth", n] => Some(Limit::Depth(n.parse().ok()?)),
+        ["movetime", ms] => Some(Limit::MoveTime(ms.parse().ok()?)),
+        ["time", time, "inc", inc] => {
+            let budget = allocate_time(millis(time)?, millis(inc)?, None);
+            Some(Limit::MoveTime(budget.as_millis()))
+        }
+        ["time", time, "inc", inc, "movestogo", n] => {
+            let budget = allocate_time(millis(time)?, millis(inc)?, Some(n.parse().ok()?));
+            Some(Limit::MoveTime(budget.as_millis()))
+        }
+        _ => None,
+    }
+}
+
+pub const MOVES_TO_GO: u32 = 30;
+pub const OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Spreads the remaining time over the moves to go (30 if unknown) and adds most of the increment.
+/// The budget never exceeds the remaining time minus some overhead for communication.
+/// Searches always finish their first iteration, so the budget may be zero.
+pub fn allocate_time(
+    remaining: Duration,
+    increment: Duration,
+    moves_to_go: Option<u32>,
+) -> Duration {
+    let moves = moves_to_go.unwrap_or(MOVES_TO_GO).max(1);
+    let budget = remaining / moves + increment * 3 / 4;
+    min(budget, remaining.saturating_sub(OVERHEAD))
+}
+
+/// Moves with equal scores are played in the order of `Move::to_u16`, see `Agent::best_child`.
+pub fn best_move(evaluator: Rc<dyn Evaluator>, game: Game, limit: Limit) -> Option<Game> {
+    best_move_with(&Agent::with_evaluator(evaluator), game, limit)
+}
+
+/// `best_move` with the settings of `agent`, a depth limit is raised by `Agent::root_depth`.
+pub fn best_move_with(agent: &Agent, game: Game, limit: Limit) -> Option<Game> {
+    let mut node = agent.new_node(game, 0);
+    let now = Instant::now();
+    loop {
+        let res = agent.bns(&mut node);
+        let done = match limit {
+            Limit::Depth(depth) => node.get_depth() >= agent.root_depth(&game, depth),
+            Limit::MoveTime(ms) => now.elapsed().as_millis() > ms,
+        };
+        if done || mate_in(node.get_lower()).is_some() || res.is_none() {
+            break;
+        }
+    }
+    let child = agent.best_child(&mut node, &game)?;
+    game.forward().nth(child as usize)
+}
+
+/// `best_move_with` for `Agent::strong`.
+pub fn strong_move(evaluator: Rc<dyn Evaluator>, game: Game, limit: Limit) -> Option<Game> {
+    aspiration_move(&Agent::strong(evaluator), game, limit)
+}
+
+/// Iterative deepening with `Agent::aspiration_search`, which uses the principal variation
+/// search and late move reductions of `agent` unlike `best_move_with`.
+/// The move is chosen like in `best_move_with`, a depth limit is raised by `Agent::root_depth`.
+pub fn aspiration_move(agent: &Agent, game: Game, limit: Limit) -> Option<Game> {
+    let mut node = agent.new_node(game, 0);
+    // decided positions are not searched, but the root needs children to choose from
+    agent.expand(&mut node)?;
+    let now = Instant::now();
+    let mut guess = 0;
+    for depth in 1.. {
+        guess = match agent.aspiration_search(&mut node, guess, depth) {
+            Some(eval) => eval,
+            None => break,
+        };
+        let done = match limit {
+            Limit::Depth(max_depth) => depth >= agent.root_depth(&game, max_depth),
+            Limit::MoveTime(ms) => now.elapsed().as_millis() > ms,
+        };
+        if done || mate_in(guess).is_some() || depth == u8::MAX {
+            break;
+        }
+    }
+    let child = agent.best_child(&mut node, &game)?;
+    game.forward().nth(child as usize)
+}
+
+pub fn run(
+    mut tablebase: impl FnMut([u32; 5]) -> Rc<TableBase>,
+    input: impl BufRead,
+    mut output: impl Write,
+) {
+    let mut state = None;
+    let mut loaded = None;
+    for line in input.lines() {
+        let line = line.unwrap();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.split_first() {
+            Some((&"position", args)) => {
+                state = parse_position(args);
+                if state.is_none() {
+                    writeln!(output, "info error invalid position").unwrap();
+                }
+            }
+            Some((&"go", args)) => {
+                let (state, limit) = match (&state, parse_go(args)) {
+                    (Some(state), Some(limit)) => (state, limit),
+                    _ => {
+                        writeln!(output, "info error invalid go").unwrap();
+                        continue;
+                    }
+                };
+                let cards = state.all_cards();
+                let table = match &loaded {
+                    Some((loaded_cards, table)) if loaded_cards == &cards => Rc::clone(table),
+                    _ => {
+                        let table = tablebase(cards);
+                        loaded = Some((cards, Rc::clone(&table)));
+                        table
+                    }
+                };
+                let game = state.game();
+                match best_move(table, game, limit) {
+                    Some(new_game) => {
+                        let flip = state.current_turn == "red";
+                        let mv = move_to_string(game, new_game, flip);
+                        writeln!(output, "bestmove {}", mv).unwrap()
+                    }
+                    None => writeln!(output, "bestmove none").unwrap(),
+                }
+            }
+            Some((&"isready", _)) => writeln!(output, "readyok").unwrap(),
+            Some((&"quit", _)) => break,
+            _ => {}
+        }
+        output.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use onitama_move_gen::{card::Card, moves::Move, perft::TEST_GAME, setup::PositionBuilder};
+
+    use super::*;
+    use crate::{
+        evaluator::{HandcraftedEvaluator, MATE, MATE_BOUND},
+        node::{Endgame, MAX_EXTENSIONS},
+    };
+
+    fn run_script(script: &str) -> String {
+        let mut output = Vec::new();
+        run(
+            |_| TableBase::empty().into(),
+            Cursor::new(script),
+            &mut output,
+        );
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_bestmove() {
+        // blue can take the red king with tiger
+        let board = "2000000100000000040000000";
+        let script = format!(
+            "position {} blue ox,crab tiger,horse eel\ngo depth 2\nquit\n",
+            board
+        );
+        assert_eq!(run_script(&script), "bestmove tiger c2c4\n");
+    }
+
+    #[test]
+    fn test_bestmove_round_trip() {
+        let board = "1121100000000000000033433";
+        for &turn in &["blue", "red"] {
+            let script = format!(
+                "position {} {} ox,crab tiger,horse eel\ngo depth 3\nquit\n",
+                board, turn
+            );
+            let output = run_script(&script);
+            let notation = output.trim().strip_prefix("bestmove ").unwrap();
+            let state = parse_position(&[board, turn, "ox,crab", "tiger,horse", "eel"]).unwrap();
+            let game = state.game();
+            let evaluator: Rc<TableBase> = TableBase::empty().into();
+            let expected = best_move(evaluator, game, Limit::Depth(3));
+            // the protocol prints the squares of red mirrored
+            let new_game = if turn == "red" {
+                let mv = Move::from_notation(notation).unwrap().mirrored();
+                game.apply_move_str(&mv.to_string())
+            } else {
+                game.apply_move_str(notation)
+            };
+            assert_eq!(new_game.ok(), expected);
+        }
+    }
+
+    #[test]
+    fn test_best_move_tie_break() {
+        // the pawn on 16 takes the king with boar, and the pawn on 18 with ox or boar
+        let game = Game {
+            my: 1 << 0 | 1 << 16 | 1 << 18,
+            other: 1 << 7 | 7 << 25,
+            cards: (1 << 0 | 1 << 1) | (1 << 2 | 1 << 3) << 16,
+            table: 4,
+        };
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        let mv = Move {
+            from: 18,
+            to: 17,
+            card: 0,
+        };
+        for _ in 0..3 {
+            let new_game = best_move(Rc::clone(&evaluator), game, Limit::Depth(3));
+            assert_eq!(new_game, Some(game.apply(mv)));
+        }
+    }
+
+    #[test]
+    fn test_strong_move() {
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+        // positions from random games with a win found at depth 4, see `lmr_finds_tactics`
+        let tactics = [
+            (134222360, 167772328, 67403840, 1),
+            (67121156, 83886852, 270533136, 2),
+            (67113494, 167772401, 1212928, 8),
+            (67113052, 335545371, 268584960, 9),
+        ];
+        for &(my, other, cards, table) in &tactics {
+            let game = Game {
+                my,
+                other,
+                cards,
+                table,
+            };
+            let new_game = strong_move(Rc::clone(&evaluator), game, Limit::Depth(4)).unwrap();
+            // every reply loses
+            let agent = Agent::with_evaluator(Rc::clone(&evaluator));
+            let mut node = agent.new_node(new_game, 0);
+            let eval = agent.search(&mut node, -MATE, MATE, 3, MAX_EXTENSIONS);
+            assert!(eval.unwrap() < -MATE_BOUND);
+        }
+
+        // the same tie break as `best_move`
+        let game = Game {
+            my: 1 << 0 | 1 << 16 | 1 << 18,
+            other: 1 << 7 | 7 << 25,
+            cards: (1 << 0 | 1 << 1) | (1 << 2 | 1 << 3) << 16,
+            table: 4,
+        };
+        assert_eq!(
+            strong_move(Rc::clone(&evaluator), game, Limit::Depth(3)),
+            best_move(Rc::clone(&evaluator), game, Limit::Depth(3))
+        );
+
+        // fewer nodes than alpha-beta with the full window at the same depth
+        let mut plain = Agent::with_evaluator(Rc::clone(&evaluator));
+        plain.set_pvs(false);
+        let mut node = plain.new_node(TEST_GAME, 0);
+        plain
+            .search(&mut node, -MATE, MATE, 5, MAX_EXTENSIONS)
+            .unwrap();
+        let strong = Agent::strong(Rc::clone(&evaluator));
+        aspiration_move(&strong, TEST_GAME, Limit::Depth(5)).unwrap();
+        assert!(strong.visited() < plain.visited());
+    }
+
+    #[test]
+    fn test_allocate_time() {
+        let ms = Duration::from_millis;
+        // no moves to go
+        assert_eq!(allocate_time(ms(60_000), ms(0), None), ms(2000));
+        assert_eq!(allocate_time(ms(60_000), ms(1000), None), ms(2750));
+        // moves to go
+        assert_eq!(allocate_time(ms(60_000), ms(0), Some(10)), ms(6000));
+        assert_eq!(allocate_time(ms(60_000), ms(0), Some(0)), ms(59_950));
+        assert_eq!(allocate_time(ms(60_000), ms(0), Some(1)), ms(59_950));
+        // very low time
+        assert_eq!(allocate_time(ms(30), ms(0), None), ms(0));
+        assert_eq!(allocate_time(ms(90), ms(0), None), ms(3));
+        // large increment
+        assert_eq!(allocate_time(ms(1000), ms(10_000), None), ms(950));
+    }
+
+    #[test]
+    fn test_go_time() {
+        match parse_go(&["time", "3000", "inc", "100", "movestogo", "3"]) {
+            Some(Limit::MoveTime(ms)) => assert_eq!(ms, 1075),
+            _ => panic!("expected move time"),
+        }
+        assert!(parse_go(&["time", "3000"]).is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let script = "go depth 1\nposition 000 blue ox\nisready\n";
+        assert_eq!(
+            run_script(script),
+            "info error invalid go\ninfo error invalid position\nreadyok\n"
+        );
+    }
+
+    #[test]
+    fn test_endgame_depth() {
+        let card = |name| Card::from_name(name).unwrap();
+        let game = PositionBuilder::new()
+            .my_king(18)
+            .my_pawn(19)
+            .other_king(22)
+            .other_pawn(10)
+            .cards([card("horse"), card("goose"), card("crab"), card("tiger")])
+            .table(card("frog"))
+            .build()
+            .unwrap();
+        let evaluator: Rc<dyn Evaluator> = Rc::new(HandcraftedEvaluator::default());
+
+        // one ply only sees the pawn move forward
+        let agent = Agent::with_evaluator(Rc::clone(&evaluator));
+        let pawn = Move {
+            from: 19,
+            to: 13,
+            card: 12,
+        };
+        let new_game = best_move_with(&agent, game, Limit::Depth(1));
+        assert_eq!(new_game, Some(game.apply(pawn)));
+
+        // the king steps next to the temple and reaches it with goose next turn
+        let mut agent = Agent::with_evaluator(evaluator);
+        agent.set_endgame(Some(Endgame {
+            pieces: 4,
+            extra_depth: 2,
+        }));
+        assert!(agent.is_endgame(&game));
+        assert_eq!(agent.root_depth(&game, 1), 3);
+        let king = Move {
+            from: 18,
+            to: 23,
+            card: 2,
+        };
+        let new_game = best_move_with(&agent, game, Limit::Depth(1));
+        assert_eq!(new_game, Some(game.apply(king)));
+    }
+}